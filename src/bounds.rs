@@ -0,0 +1,204 @@
+use crate::*;
+use crate::intersection::EPSILON;
+use std::mem::swap;
+
+// axis aligned bounding box, components may be infinite (planes, open cylinders)
+#[derive(Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+fn axis_hits(min: f64, max: f64, origin: f64, direction: f64) -> (f64, f64) {
+    if f64::abs(direction) < EPSILON {
+        // parallel to the slab, either always inside it or never
+        return if origin >= min && origin <= max {
+            (f64::NEG_INFINITY, f64::INFINITY)
+        } else {
+            (f64::INFINITY, f64::NEG_INFINITY)
+        };
+    }
+
+    let mut tmin = (min - origin) / direction;
+    let mut tmax = (max - origin) / direction;
+    if tmin > tmax {
+        swap(&mut tmin, &mut tmax);
+    }
+    (tmin, tmax)
+}
+
+impl BoundingBox {
+    pub fn new(min: Point, max: Point) -> Self {
+        BoundingBox { min, max }
+    }
+
+    // contains nothing, adding any point makes it valid
+    pub fn empty() -> Self {
+        BoundingBox {
+            min: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn infinite() -> Self {
+        BoundingBox {
+            min: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x() > self.max.x() || self.min.y() > self.max.y() || self.min.z() > self.max.z()
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.x().is_finite() && self.min.y().is_finite() && self.min.z().is_finite()
+            && self.max.x().is_finite() && self.max.y().is_finite() && self.max.z().is_finite()
+    }
+
+    pub fn add_point(&mut self, p: Point) {
+        self.min = Point::new(f64::min(self.min.x(), p.x()), f64::min(self.min.y(), p.y()), f64::min(self.min.z(), p.z()));
+        self.max = Point::new(f64::max(self.max.x(), p.x()), f64::max(self.max.y(), p.y()), f64::max(self.max.z(), p.z()));
+    }
+
+    pub fn merge(&mut self, other: &BoundingBox) {
+        if other.is_empty() {
+            return;
+        }
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    pub fn centroid(&self) -> Point {
+        Point::new(
+            (self.min.x() + self.max.x()) / 2.0,
+            (self.min.y() + self.max.y()) / 2.0,
+            (self.min.z() + self.max.z()) / 2.0,
+        )
+    }
+
+    // box around the 8 transformed corners; an infinite box stays infinite
+    // since rotating it would mix infinities into NaNs
+    pub fn transform(&self, t: &Transform) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            return BoundingBox::infinite();
+        }
+
+        let corners = [
+            Point::new(self.min.x(), self.min.y(), self.min.z()),
+            Point::new(self.min.x(), self.min.y(), self.max.z()),
+            Point::new(self.min.x(), self.max.y(), self.min.z()),
+            Point::new(self.min.x(), self.max.y(), self.max.z()),
+            Point::new(self.max.x(), self.min.y(), self.min.z()),
+            Point::new(self.max.x(), self.min.y(), self.max.z()),
+            Point::new(self.max.x(), self.max.y(), self.min.z()),
+            Point::new(self.max.x(), self.max.y(), self.max.z()),
+        ];
+        let mut result = BoundingBox::empty();
+        for corner in corners.iter() {
+            result.add_point(t.clone() * *corner);
+        }
+        result
+    }
+
+    // tests the whole line, not just t > 0, so hits behind the origin that
+    // prepare_computations relies on for refraction are never culled
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let (x_min, x_max) = axis_hits(self.min.x(), self.max.x(), ray.origin().x(), ray.direction().x());
+        let (y_min, y_max) = axis_hits(self.min.y(), self.max.y(), ray.origin().y(), ray.direction().y());
+        let (z_min, z_max) = axis_hits(self.min.z(), self.max.z(), ray.origin().z(), ray.direction().z());
+
+        let tmin = f64::max(f64::max(x_min, y_min), z_min);
+        let tmax = f64::min(f64::min(x_max, y_max), z_max);
+        tmin <= tmax
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape_props::ShapeProperties;
+    use crate::sphere::sphere;
+    use crate::plane::plane;
+    use crate::cylinder::cylinder;
+    use crate::group::group;
+    use std::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
+
+    #[test]
+    fn adding_points_to_an_empty_bounding_box() {
+        let mut b = BoundingBox::empty();
+        assert!(b.is_empty());
+        b.add_point(Point::new(-5.0, 2.0, 0.0));
+        b.add_point(Point::new(7.0, 0.0, -3.0));
+        assert!(b.min == Point::new(-5.0, 0.0, -3.0));
+        assert!(b.max == Point::new(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn merging_two_bounding_boxes() {
+        let mut a = BoundingBox::new(Point::new(-5.0, -2.0, 0.0), Point::new(7.0, 4.0, 4.0));
+        let b = BoundingBox::new(Point::new(8.0, -7.0, -2.0), Point::new(14.0, 2.0, 8.0));
+        a.merge(&b);
+        assert!(a.min == Point::new(-5.0, -7.0, -2.0));
+        assert!(a.max == Point::new(14.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn transforming_a_bounding_box() {
+        let b = BoundingBox::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let t = Transform::new().rotate_y(PI / 4.0).rotate_x(PI / 4.0);
+        let b2 = b.transform(&t);
+        let (a, b) = (SQRT_2, 1.0 + FRAC_1_SQRT_2);
+        assert!(b2.min == Point::new(-a, -b, -b));
+        assert!(b2.max == Point::new(a, b, b));
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_a_bounding_box() {
+        let b = BoundingBox::new(Point::new(5.0, -2.0, 0.0), Point::new(11.0, 4.0, 7.0));
+        let hit = Ray::new(Point::new(15.0, 1.0, 2.0), Vector::new(-1.0, 0.0, 0.0));
+        let miss = Ray::new(Point::new(15.0, 20.0, 2.0), Vector::new(-1.0, 0.0, 0.0));
+        let inside = Ray::new(Point::new(8.0, 1.0, 3.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(b.intersects(&hit));
+        assert!(!b.intersects(&miss));
+        assert!(b.intersects(&inside));
+    }
+
+    #[test]
+    fn bounds_of_primitives_honor_their_transform() {
+        let mut s = sphere();
+        s.set_transform(Transform::new().scaling(2.0, 2.0, 2.0).translate(1.0, 0.0, 0.0));
+        let b = s.bounds();
+        assert!(b.min == Point::new(-1.0, -2.0, -2.0));
+        assert!(b.max == Point::new(3.0, 2.0, 2.0));
+
+        let p = plane();
+        assert!(!p.bounds().is_finite());
+
+        let mut c = cylinder();
+        c.set_cylinder_truncation(-1.0, 3.0);
+        let b = c.bounds();
+        assert!(b.min == Point::new(-1.0, -1.0, -1.0));
+        assert!(b.max == Point::new(1.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn a_group_is_bounded_by_its_children() {
+        let mut s1 = sphere();
+        s1.set_transform(Transform::new().translate(2.0, 5.0, -3.0));
+        let mut s2 = sphere();
+        s2.set_transform(Transform::new().scaling(0.5, 0.5, 0.5).translate(-4.0, -1.0, 4.0));
+        let mut g = group();
+        g.add_child(Box::new(s1));
+        g.add_child(Box::new(s2));
+        let b = g.bounds();
+        assert!(b.min == Point::new(-4.5, -1.5, -4.0));
+        assert!(b.max == Point::new(3.0, 6.0, 4.5));
+    }
+}
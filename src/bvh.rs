@@ -0,0 +1,192 @@
+use crate::*;
+use crate::bounds::BoundingBox;
+use crate::shape_props::ShapeProperties;
+use crate::intersection::Intersection;
+use std::cmp::Ordering::Equal;
+
+// leaves stop splitting once they hold this many shapes
+const LEAF_SIZE: usize = 4;
+
+enum BvhNode {
+    Leaf {
+        bounds: BoundingBox,
+        items: Vec<usize>,
+    },
+    Interior {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+// hierarchy over indices into a list of shapes owned by someone else (the
+// world or a group), so the shapes themselves never move; a mesh builds one
+// over the boxes of its triangles
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // shapes with infinite bounds (planes) are tested against every ray
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    // median split along the axis where the centroids spread the most
+    pub fn build(objects: &[Box<dyn ShapeProperties>]) -> Bvh {
+        let bounds: Vec<BoundingBox> = objects.iter().map(|object| object.bounds()).collect();
+        Bvh::build_from_bounds(&bounds)
+    }
+
+    pub fn build_from_bounds(boxes: &[BoundingBox]) -> Bvh {
+        let mut bvh = Bvh { nodes: vec![], unbounded: vec![] };
+        let mut items: Vec<(usize, BoundingBox)> = vec![];
+        for (index, bounds) in boxes.iter().enumerate() {
+            let bounds = *bounds;
+            if bounds.is_finite() {
+                items.push((index, bounds));
+            } else {
+                bvh.unbounded.push(index);
+            }
+        }
+
+        if !items.is_empty() {
+            bvh.build_node(items);
+        }
+        bvh
+    }
+
+    pub fn bounds(&self) -> BoundingBox {
+        if !self.unbounded.is_empty() {
+            return BoundingBox::infinite();
+        }
+        match self.nodes.first() {
+            Some(BvhNode::Leaf { bounds, .. }) => *bounds,
+            Some(BvhNode::Interior { bounds, .. }) => *bounds,
+            None => BoundingBox::empty(),
+        }
+    }
+
+    fn build_node(&mut self, mut items: Vec<(usize, BoundingBox)>) -> usize {
+        let mut bounds = BoundingBox::empty();
+        let mut centroids = BoundingBox::empty();
+        for (_, b) in &items {
+            bounds.merge(b);
+            centroids.add_point(b.centroid());
+        }
+
+        let index = self.nodes.len();
+        if items.len() <= LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf { bounds, items: items.iter().map(|(i, _)| *i).collect() });
+            return index;
+        }
+
+        let extent = centroids.max - centroids.min;
+        let axis_of = |p: Point| -> f64 {
+            if extent.x() >= extent.y() && extent.x() >= extent.z() {
+                p.x()
+            } else if extent.y() >= extent.z() {
+                p.y()
+            } else {
+                p.z()
+            }
+        };
+        items.sort_by(|a, b| axis_of(a.1.centroid()).partial_cmp(&axis_of(b.1.centroid())).unwrap_or(Equal));
+        let right_items = items.split_off(items.len() / 2);
+
+        // reserve our slot first so the root always sits at index 0
+        self.nodes.push(BvhNode::Leaf { bounds, items: vec![] });
+        let left = self.build_node(items);
+        let right = self.build_node(right_items);
+        self.nodes[index] = BvhNode::Interior { bounds, left, right };
+        index
+    }
+
+    // unsorted hits of every shape whose box the ray passes through
+    pub fn intersect<'a>(&self, objects: &'a [Box<dyn ShapeProperties>], ray: &Ray) -> Vec<Intersection<'a>> {
+        let mut result: Vec<Intersection> = vec![];
+        self.traverse(ray, |index| {
+            let mut xs = objects[index].intersect(ray);
+            result.append(&mut xs);
+        });
+        result
+    }
+
+    // calls visit with the index of every item whose box the ray may hit
+    pub fn traverse<F: FnMut(usize)>(&self, ray: &Ray, mut visit: F) {
+        for index in &self.unbounded {
+            visit(*index);
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            match &self.nodes[node_index] {
+                BvhNode::Leaf { bounds, items } => {
+                    if !bounds.intersects(ray) {
+                        continue;
+                    }
+                    for index in items {
+                        visit(*index);
+                    }
+                }
+                BvhNode::Interior { bounds, left, right } => {
+                    if bounds.intersects(ray) {
+                        stack.push(*left);
+                        stack.push(*right);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::sphere;
+    use crate::plane::plane;
+    use crate::material::float_eq;
+
+    fn row_of_spheres(count: usize) -> Vec<Box<dyn ShapeProperties>> {
+        let mut objects: Vec<Box<dyn ShapeProperties>> = vec![];
+        for i in 0..count {
+            let mut s = sphere();
+            s.set_transform(Transform::new().translate(3.0 * i as f64, 0.0, 0.0));
+            objects.push(Box::new(s));
+        }
+        objects
+    }
+
+    #[test]
+    fn a_bvh_finds_the_same_hits_as_a_linear_scan() {
+        let objects = row_of_spheres(20);
+        let bvh = Bvh::build(&objects);
+
+        let r = Ray::new(Point::new(27.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = bvh.intersect(&objects, &r);
+        assert_eq!(xs.len(), 2);
+        assert!(float_eq(xs[0].t, 4.0) || float_eq(xs[0].t, 6.0));
+
+        let along = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(bvh.intersect(&objects, &along).len(), 40);
+
+        let miss = Ray::new(Point::new(27.0, 5.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(bvh.intersect(&objects, &miss).len(), 0);
+    }
+
+    #[test]
+    fn unbounded_shapes_are_always_tested() {
+        let mut objects = row_of_spheres(8);
+        let mut p = plane();
+        p.set_transform(Transform::new().translate(0.0, -1.0, 0.0));
+        objects.push(Box::new(p));
+        let bvh = Bvh::build(&objects);
+        assert!(!bvh.bounds().is_finite());
+
+        let r = Ray::new(Point::new(100.0, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let xs = bvh.intersect(&objects, &r);
+        assert_eq!(xs.len(), 1);
+        assert!(float_eq(xs[0].t, 11.0));
+    }
+}
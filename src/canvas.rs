@@ -4,47 +4,394 @@ use image::ColorType;
 use image::save_buffer_with_format;
 use image::EncodableLayout;
 use self::image::{Rgba, RgbaImage};
+use std::fmt;
+use std::fs;
+use std::path::Path;
 
 type Color = Tuple;
 
+// how linear radiance above 1.0 is squeezed into the displayable range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // anything brighter than white is cut off
+    Clamp,
+    // c / (1 + c), never quite reaches white
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(&self, c: f64) -> f64 {
+        let c = f64::max(c, 0.0);
+        let mapped = match self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => c / (1.0 + c),
+            ToneMap::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+// linear [0, 1] to the sRGB transfer curve image viewers expect
+pub fn srgb_encode(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// inverse of srgb_encode
+pub fn srgb_decode(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn error<T>(message: String) -> Result<T, CanvasError> {
+    Err(CanvasError { message })
+}
+
+// reads the whitespace separated header fields of a ppm, skipping comments
+struct PpmReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PpmReader<'a> {
+    fn skip_whitespace_and_comments(&mut self) {
+        while self.pos < self.data.len() {
+            let c = self.data[self.pos];
+            if c == b'#' {
+                while self.pos < self.data.len() && self.data[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a str> {
+        self.skip_whitespace_and_comments();
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() && self.data[self.pos] != b'#' {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        std::str::from_utf8(&self.data[start..self.pos]).ok()
+    }
+
+    fn number(&mut self, what: &str) -> Result<u32, CanvasError> {
+        match self.token().map(|t| t.parse::<u32>()) {
+            Some(Ok(n)) => Ok(n),
+            _ => error(format!("ppm: expected {}", what)),
+        }
+    }
+}
+
+// plain PPM lines may not be longer than this
+const PPM_LINE_WIDTH: usize = 70;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileFormat {
+    // plain text P3 ppm
+    PpmAscii,
+    // binary P6 ppm
+    PpmBinary,
+    Png,
+    // portable float map, keeps the linear colors (only exposure is applied)
+    Pfm,
+}
+
+impl FileFormat {
+    // .ppm is the plain text variant, P6 has to be asked for explicitly
+    pub fn from_path(file: &str) -> Option<FileFormat> {
+        let extension = Path::new(file).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(FileFormat::PpmAscii),
+            "png" => Some(FileFormat::Png),
+            "pfm" => Some(FileFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CanvasError {
+    pub message: String,
+}
+
+impl fmt::Display for CanvasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// pixels are kept as linear floating point colors, exposure, tone mapping and
+// gamma are only applied when the image is exported
 pub struct Canvas {
     width: u32,
     height: u32,
-    pixels: RgbaImage
+    pixels: Vec<Color>,
+    // multiplies every color before tone mapping
+    pub exposure: f64,
+    pub tone_map: ToneMap,
 }
 
 pub trait CanvasProperties {
     fn new(width: u32, height: u32) -> Canvas;
 
+    // P3 or P6, values are scaled by the max value into [0, 1] as stored
+    fn from_ppm(data: &[u8]) -> Result<Canvas, CanvasError>;
+
+    // ppm by extension, anything else (png, jpeg, ...) through the image crate
+    fn load(file: &str) -> Result<Canvas, CanvasError>;
+
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    // undo the sRGB encoding of a loaded 8 bit image so it can be used as linear color
+    fn linearize(&mut self);
+
     fn write_pixel(&mut self, x: u32, y: u32, color: Color);
 
+    // the linear color as written, not tone mapped
     fn pixel_at(&self, x: u32, y: u32) -> Color;
 
-    fn to_ppm(&self, file: &str);
+    // 8 bit sRGB version of the pixel after exposure and tone mapping
+    fn display_pixel(&self, x: u32, y: u32) -> [u8; 4];
+
+    fn to_rgba_image(&self) -> RgbaImage;
+
+    // P3 text with lines wrapped at 70 columns
+    fn ppm_ascii(&self) -> String;
+
+    fn ppm_binary(&self) -> Vec<u8>;
+
+    fn pfm(&self) -> Vec<u8>;
+
+    // format picked from the file extension
+    fn save(&self, file: &str) -> Result<(), CanvasError>;
+
+    fn save_as(&self, file: &str, format: FileFormat) -> Result<(), CanvasError>;
 }
 
 impl CanvasProperties for Canvas {
     fn new(width: u32, height: u32) -> Canvas {
-        Canvas {width, height, pixels: RgbaImage::new(width , height)}
+        Canvas {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0, 0.0); (width * height) as usize],
+            exposure: 1.0,
+            tone_map: ToneMap::Clamp
+        }
+    }
+
+    fn from_ppm(data: &[u8]) -> Result<Canvas, CanvasError> {
+        let mut reader = PpmReader { data, pos: 0 };
+        let binary = match reader.token() {
+            Some("P3") => false,
+            Some("P6") => true,
+            _ => return error("ppm: expected P3 or P6 magic number".to_string()),
+        };
+        let width = reader.number("width")?;
+        let height = reader.number("height")?;
+        let max_value = reader.number("max value")?;
+        if max_value == 0 || max_value > 65535 {
+            return error(format!("ppm: invalid max value {}", max_value));
+        }
+
+        // the header is untrusted, a canvas this size could not be indexed
+        let count = match width.checked_mul(height).and_then(|n| n.checked_mul(3)) {
+            Some(count) => count as usize,
+            None => return error(format!("ppm: image of {} x {} is too large", width, height)),
+        };
+        // every sample takes at least one byte, don't reserve more than the data can fill
+        let mut values: Vec<u32> = Vec::with_capacity(usize::min(count, data.len()));
+        if binary {
+            // exactly one whitespace byte separates the header from the samples
+            let mut pos = reader.pos + 1;
+            let wide = max_value > 255;
+            for _ in 0..count {
+                if wide {
+                    if pos + 1 >= data.len() {
+                        break;
+                    }
+                    values.push(((data[pos] as u32) << 8) | data[pos + 1] as u32);
+                    pos += 2;
+                } else {
+                    if pos >= data.len() {
+                        break;
+                    }
+                    values.push(data[pos] as u32);
+                    pos += 1;
+                }
+            }
+        } else {
+            for _ in 0..count {
+                match reader.token() {
+                    Some(token) => match token.parse::<u32>() {
+                        Ok(v) => values.push(v),
+                        Err(_) => return error(format!("ppm: invalid sample '{}'", token)),
+                    },
+                    None => break,
+                }
+            }
+        }
+        if values.len() < count {
+            return error(format!("ppm: expected {} samples, found {}", count, values.len()));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let scale = max_value as f64;
+        for (i, rgb) in values.chunks(3).enumerate() {
+            let color = Color::new(rgb[0] as f64 / scale, rgb[1] as f64 / scale, rgb[2] as f64 / scale, 0.0);
+            canvas.pixels[i] = color;
+        }
+        Ok(canvas)
+    }
+
+    fn load(file: &str) -> Result<Canvas, CanvasError> {
+        let is_ppm = Path::new(file).extension().is_some_and(|e| e.eq_ignore_ascii_case("ppm"));
+        if is_ppm {
+            let data = fs::read(file).map_err(|e| CanvasError { message: format!("{}: {}", file, e) })?;
+            return Canvas::from_ppm(&data).map_err(|e| CanvasError { message: format!("{}: {}", file, e) });
+        }
+
+        let image = match image::open(file) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => return error(format!("{}: {}", file, e)),
+        };
+        let mut canvas = Canvas::new(image.width(), image.height());
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let color = Color::new(pixel[0] as f64 / 255.0, pixel[1] as f64 / 255.0, pixel[2] as f64 / 255.0, 0.0);
+            canvas.write_pixel(x, y, color);
+        }
+        Ok(canvas)
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn linearize(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = Color::new(srgb_decode(pixel.x), srgb_decode(pixel.y), srgb_decode(pixel.z), pixel.w);
+        }
     }
 
     fn write_pixel(&mut self, x: u32, y: u32, color: Color) {
-        let pixel = Rgba([(color.x as u8), (color.y as u8), (color.z as u8), (color.w as u8)]);
-        self.pixels.put_pixel(x, y, pixel);
+        let index = (y * self.width + x) as usize;
+        self.pixels[index] = color;
     }
 
     fn pixel_at(&self, x: u32, y: u32) -> Color {
         if x >= self.width || y >= self.height {
-            let pixel = self.pixels.get_pixel((self.width -1 ) as u32, (self.height - 1) as u32);
-            return Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64, pixel[3] as f64);
+            return self.pixels[self.pixels.len() - 1];
         }
-        let pixel = self.pixels.get_pixel(x, y);
-        Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64, pixel[3] as f64)
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    fn display_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let color = self.pixel_at(x, y);
+        let encode = |c: f64| -> u8 {
+            let display = srgb_encode(self.tone_map.apply(c * self.exposure));
+            f64::round(display * 255.0) as u8
+        };
+        [encode(color.x), encode(color.y), encode(color.z), 255]
     }
 
-    fn to_ppm(&self, file: &str) {
-        save_buffer_with_format(file, self.pixels.as_bytes(), self.pixels.width(), self.pixels.height(),
-                                ColorType::Rgba8, image::ImageFormat::Png).unwrap()
+    fn to_rgba_image(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.put_pixel(x, y, Rgba(self.display_pixel(x, y)));
+            }
+        }
+        image
+    }
+
+    fn ppm_ascii(&self) -> String {
+        let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for y in 0..self.height {
+            let mut line = String::new();
+            for x in 0..self.width {
+                let pixel = self.display_pixel(x, y);
+                for value in pixel[..3].iter() {
+                    let token = value.to_string();
+                    if !line.is_empty() && line.len() + 1 + token.len() > PPM_LINE_WIDTH {
+                        ppm.push_str(&line);
+                        ppm.push('\n');
+                        line.clear();
+                    }
+                    if !line.is_empty() {
+                        line.push(' ');
+                    }
+                    line.push_str(&token);
+                }
+            }
+            ppm.push_str(&line);
+            ppm.push('\n');
+        }
+        ppm
+    }
+
+    fn ppm_binary(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                ppm.extend_from_slice(&self.display_pixel(x, y)[..3]);
+            }
+        }
+        ppm
+    }
+
+    fn pfm(&self) -> Vec<u8> {
+        // a negative scale marks little endian data, rows run bottom to top
+        let mut pfm = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.pixel_at(x, y) * self.exposure;
+                for value in [color.x, color.y, color.z].iter() {
+                    pfm.extend_from_slice(&(*value as f32).to_le_bytes());
+                }
+            }
+        }
+        pfm
+    }
+
+    fn save(&self, file: &str) -> Result<(), CanvasError> {
+        match FileFormat::from_path(file) {
+            Some(format) => self.save_as(file, format),
+            None => Err(CanvasError { message: format!("{}: unknown image format, use .ppm, .png or .pfm", file) }),
+        }
+    }
+
+    fn save_as(&self, file: &str, format: FileFormat) -> Result<(), CanvasError> {
+        let written = match format {
+            FileFormat::PpmAscii => fs::write(file, self.ppm_ascii()).map_err(|e| e.to_string()),
+            FileFormat::PpmBinary => fs::write(file, self.ppm_binary()).map_err(|e| e.to_string()),
+            FileFormat::Pfm => fs::write(file, self.pfm()).map_err(|e| e.to_string()),
+            FileFormat::Png => {
+                let image = self.to_rgba_image();
+                save_buffer_with_format(file, image.as_bytes(), image.width(), image.height(),
+                                        ColorType::Rgba8, image::ImageFormat::Png).map_err(|e| e.to_string())
+            }
+        };
+        written.map_err(|message| CanvasError { message: format!("{}: {}", file, message) })
     }
 }
 
@@ -76,4 +423,219 @@ mod tests {
         let p = canvas.pixel_at(1,1);
         assert_eq!(f64::abs(p.x - 2.0) <= 0.01, true);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn colors_are_stored_in_floating_point() {
+        let mut canvas = Canvas::new(4, 2);
+        canvas.write_pixel(3, 1, Color::new(0.25, 17.5, 0.001, 0.0));
+        let p = canvas.pixel_at(3, 1);
+        assert_eq!(p.x, 0.25);
+        assert_eq!(p.y, 17.5);
+        assert_eq!(p.z, 0.001);
+    }
+
+    #[test]
+    fn tone_mapping_operators() {
+        assert_eq!(ToneMap::Clamp.apply(0.5), 0.5);
+        assert_eq!(ToneMap::Clamp.apply(3.0), 1.0);
+        assert_eq!(ToneMap::Clamp.apply(-1.0), 0.0);
+        assert_eq!(ToneMap::Reinhard.apply(1.0), 0.5);
+        assert!(ToneMap::Reinhard.apply(100.0) < 1.0);
+        assert!(f64::abs(ToneMap::Aces.apply(0.18) - 0.2673) < 0.001);
+        assert_eq!(ToneMap::Aces.apply(100.0), 1.0);
+    }
+
+    #[test]
+    fn exported_pixels_are_exposed_tone_mapped_and_gamma_encoded() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.write_pixel(0, 0, Color::new(0.0, 0.5, 1.0, 0.0));
+        // sRGB(0.5) is 0.7354, i.e. 188
+        assert_eq!(canvas.display_pixel(0, 0), [0, 188, 255, 255]);
+
+        canvas.exposure = 0.5;
+        assert_eq!(canvas.display_pixel(0, 0), [0, 137, 188, 255]);
+
+        canvas.exposure = 1.0;
+        canvas.tone_map = ToneMap::Reinhard;
+        assert_eq!(canvas.display_pixel(0, 0), [0, 156, 188, 255]);
+    }
+
+    #[test]
+    fn constructing_the_ppm_header() {
+        let canvas = Canvas::new(5, 3);
+        let ppm = canvas.ppm_ascii();
+        let lines: Vec<&str> = ppm.lines().collect();
+        assert_eq!(lines[0], "P3");
+        assert_eq!(lines[1], "5 3");
+        assert_eq!(lines[2], "255");
+    }
+
+    #[test]
+    fn constructing_the_ppm_pixel_data() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0, 0.0));
+        canvas.write_pixel(2, 1, Color::new(0.0, 0.5, 0.0, 0.0));
+        canvas.write_pixel(4, 2, Color::new(-0.5, 0.0, 1.0, 0.0));
+        let ppm = canvas.ppm_ascii();
+        let lines: Vec<&str> = ppm.lines().collect();
+        assert_eq!(lines[3], "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0");
+        assert_eq!(lines[4], "0 0 0 0 0 0 0 188 0 0 0 0 0 0 0");
+        assert_eq!(lines[5], "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255");
+    }
+
+    #[test]
+    fn splitting_long_lines_in_ppm_files() {
+        let mut canvas = Canvas::new(10, 2);
+        for y in 0..2 {
+            for x in 0..10 {
+                canvas.write_pixel(x, y, Color::new(1.0, 0.8, 0.6, 0.0));
+            }
+        }
+        let ppm = canvas.ppm_ascii();
+        let lines: Vec<&str> = ppm.lines().collect();
+        assert_eq!(lines[3], "255 231 203 255 231 203 255 231 203 255 231 203 255 231 203 255 231");
+        assert_eq!(lines[4], "203 255 231 203 255 231 203 255 231 203 255 231 203");
+        assert_eq!(lines.len(), 7);
+        assert!(lines.iter().all(|l| l.len() <= 70));
+    }
+
+    #[test]
+    fn ppm_files_are_terminated_by_a_newline() {
+        let canvas = Canvas::new(5, 3);
+        assert!(canvas.ppm_ascii().ends_with('\n'));
+    }
+
+    #[test]
+    fn binary_ppm_and_pfm_layouts() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(1, 0, Color::new(1.0, 0.0, 2.5, 0.0));
+
+        let p6 = canvas.ppm_binary();
+        assert!(p6.starts_with(b"P6\n2 1\n255\n"));
+        assert_eq!(&p6[p6.len() - 6..], &[0, 0, 0, 255, 0, 255]);
+
+        let pfm = canvas.pfm();
+        let header = b"PF\n2 1\n-1.0\n";
+        assert!(pfm.starts_with(header));
+        assert_eq!(pfm.len(), header.len() + 2 * 3 * 4);
+        let last = &pfm[pfm.len() - 4..];
+        assert_eq!(f32::from_le_bytes([last[0], last[1], last[2], last[3]]), 2.5);
+    }
+
+    #[test]
+    fn the_format_follows_the_file_extension() {
+        assert_eq!(FileFormat::from_path("out.ppm"), Some(FileFormat::PpmAscii));
+        assert_eq!(FileFormat::from_path("renders/out.PNG"), Some(FileFormat::Png));
+        assert_eq!(FileFormat::from_path("out.pfm"), Some(FileFormat::Pfm));
+        assert_eq!(FileFormat::from_path("out.tiff"), None);
+
+        let canvas = Canvas::new(1, 1);
+        assert!(canvas.save("out.tiff").is_err());
+        assert!(canvas.save("/nonexistent/dir/out.ppm").is_err());
+    }
+
+    #[test]
+    fn reading_a_file_with_the_wrong_magic_number() {
+        let ppm = "P32\n1 1\n255\n0 0 0\n";
+        assert!(Canvas::from_ppm(ppm.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reading_a_ppm_returns_a_canvas_of_the_right_size() {
+        let ppm = "P3\n10 2\n255\n".to_string() + &"0 0 0 ".repeat(20);
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.width(), 10);
+        assert_eq!(canvas.height(), 2);
+    }
+
+    #[test]
+    fn reading_pixel_data_from_a_ppm_file() {
+        let ppm = "P3
+4 3
+255
+255 127 0  0 127 255  127 255 0  255 255 255
+0 0 0  255 0 0  0 255 0  0 0 255
+255 255 0  0 255 255  255 0 255  127 127 127
+";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert!(canvas.pixel_at(0, 0) == Color::new(1.0, 0.498, 0.0, 0.0));
+        assert!(canvas.pixel_at(1, 0) == Color::new(0.0, 0.498, 1.0, 0.0));
+        assert!(canvas.pixel_at(3, 1) == Color::new(0.0, 0.0, 1.0, 0.0));
+        assert!(canvas.pixel_at(3, 2) == Color::new(0.498, 0.498, 0.498, 0.0));
+    }
+
+    #[test]
+    fn ppm_parsing_ignores_comment_lines() {
+        let ppm = "P3
+# this is a comment
+2 1
+# this, too
+255
+# another comment
+255 255 255
+# oh, no, comments in the pixel data!
+255 0 255
+";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert!(canvas.pixel_at(0, 0) == Color::new(1.0, 1.0, 1.0, 0.0));
+        assert!(canvas.pixel_at(1, 0) == Color::new(1.0, 0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ppm_parsing_allows_an_rgb_triple_to_span_lines() {
+        let ppm = "P3\n1 1\n255\n51\n153\n\n204\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert!(canvas.pixel_at(0, 0) == Color::new(0.2, 0.6, 0.8, 0.0));
+    }
+
+    #[test]
+    fn ppm_parsing_respects_the_scale_setting() {
+        let ppm = "P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert!(canvas.pixel_at(0, 1) == Color::new(0.75, 0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn ppm_dimensions_too_large_for_a_canvas_are_an_error() {
+        let ppm = "P3\n100000 100000\n255\n0 0 0\n";
+        let result = Canvas::from_ppm(ppm.as_bytes());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reading_binary_ppm_files() {
+        let mut p6 = b"P6\n# binary\n2 1\n255\n".to_vec();
+        p6.extend_from_slice(&[255, 0, 51, 0, 255, 204]);
+        let canvas = Canvas::from_ppm(&p6).unwrap();
+        assert!(canvas.pixel_at(0, 0) == Color::new(1.0, 0.0, 0.2, 0.0));
+        assert!(canvas.pixel_at(1, 0) == Color::new(0.0, 1.0, 0.8, 0.0));
+
+        // two bytes per sample, big endian, when the max value needs them
+        let mut wide = b"P6 1 1 1000\n".to_vec();
+        wide.extend_from_slice(&[0x03, 0xe8, 0x01, 0xf4, 0x00, 0x00]);
+        let canvas = Canvas::from_ppm(&wide).unwrap();
+        assert!(canvas.pixel_at(0, 0) == Color::new(1.0, 0.5, 0.0, 0.0));
+
+        let truncated = b"P6 2 1 255\n\x01\x02".to_vec();
+        assert!(Canvas::from_ppm(&truncated).is_err());
+    }
+
+    #[test]
+    fn a_saved_image_can_be_loaded_back() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(2, 1, Color::new(0.5, 0.25, 1.0, 0.0));
+        let dir = std::env::temp_dir();
+        for name in ["canvas_round_trip.ppm", "canvas_round_trip.png"].iter() {
+            let file = dir.join(name);
+            let file = file.to_str().unwrap();
+            canvas.save(file).unwrap();
+            let mut loaded = Canvas::load(file).unwrap();
+            loaded.linearize();
+            // 8 bits only get within a few thousandths
+            let p = loaded.pixel_at(2, 1);
+            assert!(f64::abs(p.x - 0.5) < 0.005 && f64::abs(p.y - 0.25) < 0.005 && f64::abs(p.z - 1.0) < 0.005);
+            assert!(loaded.pixel_at(0, 0) == Color::new(0.0, 0.0, 0.0, 0.0));
+            let _ = fs::remove_file(file);
+        }
+    }
+}
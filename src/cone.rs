@@ -1,13 +1,14 @@
 use crate::*;
 use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
 use crate::intersection::Intersection;
-use std::rc::Rc;
 use std::mem::swap;
 
 #[derive(Clone)]
 pub struct Cone {
     pub material: Material,
     pub transform: Transform,
+    pub parent_transform: Transform,
     pub cylinder_minimum: f64,
     pub cylinder_maximum: f64,
     pub cylinder_closed: bool,
@@ -22,8 +23,16 @@ impl ShapeProperties for Cone {
         self.transform = t;
     }
 
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
     fn normal_at(&self, p: Point) -> Vector {
-        let local_point = self.transform.inverse() * p;
+        let local_point = self.world_to_object(p);
         let mut normal_obj_space: Vector;
         let dist2 = local_point.x() * local_point.x() + local_point.z() * local_point.z();
         let dist = f64::sqrt(dist2);
@@ -43,9 +52,13 @@ impl ShapeProperties for Cone {
             normal_obj_space = Vector::new(local_point.x(), dist, local_point.z())
         }
 
-        let mut world_normal = self.transform.inverse().transpose() * normal_obj_space;
-        world_normal.data.w = 0.0;
-        return world_normal.normalize();
+        self.normal_to_world(normal_obj_space)
+    }
+
+    // the caps reuse the side's frame, seen from above
+    fn tangents_at(&self, p: Point) -> (Vector, Vector) {
+        let (u, v) = shape_props::tangents_around_y(self.world_to_object(p));
+        (self.vector_to_world(u), self.vector_to_world(v))
     }
 
     fn intersect(&self, ray_world: &Ray) -> Vec<Intersection> {
@@ -60,20 +73,15 @@ impl ShapeProperties for Cone {
             let t = (min - ray_obj.origin().y()) / ray_obj.direction().y();
             if shape_props::check_cap_cone(&ray_obj, t, min) {
                 result.push(
-                    Intersection {
-                        t,
-                        object: self.as_trait()
-                    }
+                    Intersection::new(t, self.as_trait())
                 );
             }
 
             let t = (max - ray_obj.origin().y()) / ray_obj.direction().y();
             if shape_props::check_cap_cone(&ray_obj, t, max) {
                 result.push(
-                    Intersection {
-                    t,
-                        object: self.as_trait()
-                });
+                    Intersection::new(t, self.as_trait())
+                );
             }
         }
 
@@ -99,10 +107,7 @@ impl ShapeProperties for Cone {
             let y = t * dy + oy;
             if y > min && y < max {
                 result.push(
-                    Intersection {
-                        t,
-                        object: self.as_trait()
-                    }
+                    Intersection::new(t, self.as_trait())
                 );
             }
         }
@@ -118,26 +123,29 @@ impl ShapeProperties for Cone {
             let y0 = t0 * dy + oy;
             if y0 > min && y0 < max {
                 result.push(
-                    Intersection {
-                        t: t0,
-                        object: self.as_trait()
-                    }
+                    Intersection::new(t0, self.as_trait())
                 );
             }
 
             let y1 = t1 * dy + oy;
             if y1 > min && y1 < max {
                 result.push(
-                    Intersection {
-                        t: t1,
-                        object: self.as_trait()
-                    }
+                    Intersection::new(t1, self.as_trait())
                 );
             }
         }
         return result;
     }
 
+    fn local_bounds(&self) -> BoundingBox {
+        if self.cylinder_minimum == f64::MIN || self.cylinder_maximum == f64::MAX {
+            return BoundingBox::infinite();
+        }
+        // radius at y is abs(y)
+        let r = f64::max(f64::abs(self.cylinder_minimum), f64::abs(self.cylinder_maximum));
+        BoundingBox::new(Point::new(-r, self.cylinder_minimum, -r), Point::new(r, self.cylinder_maximum, r))
+    }
+
     fn as_trait(&self) -> &dyn ShapeProperties {
         self
     }
@@ -152,6 +160,28 @@ impl ShapeProperties for Cone {
 }
 
 
+impl Cone {
+    pub fn new() -> Self {
+        Cone {
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            material: Material::default(),
+            cylinder_maximum: f64::MAX,
+            cylinder_minimum: f64::MIN,
+            cylinder_closed: false
+        }
+    }
+
+    pub fn set_cylinder_truncation(&mut self, min: f64, max: f64) {
+        self.cylinder_minimum = min;
+        self.cylinder_maximum = max;
+    }
+
+    pub fn set_cylinder_closed(&mut self, closed: bool) {
+        self.cylinder_closed = closed;
+    }
+}
+
 mod tests {
 
     //     #[test]
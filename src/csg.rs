@@ -0,0 +1,284 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::intersection::Intersection;
+use crate::bounds::BoundingBox;
+use std::cmp::Ordering::Equal;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+// lhit: the hit is on the left child
+// inl, inr: whether the ray is currently inside the left / right child
+pub fn intersection_allowed(op: Operation, lhit: bool, inl: bool, inr: bool) -> bool {
+    match op {
+        Operation::Union => (lhit && !inr) || (!lhit && !inl),
+        Operation::Intersection => (lhit && inr) || (!lhit && inl),
+        Operation::Difference => (lhit && !inr) || (!lhit && inl),
+    }
+}
+
+// like a group, a csg is never hit itself: its hits refer to leaves of either child
+pub struct Csg {
+    pub operation: Operation,
+    pub material: Material,
+    pub transform: Transform,
+    pub parent_transform: Transform,
+    pub left: Box<dyn ShapeProperties>,
+    pub right: Box<dyn ShapeProperties>,
+    // same rule as a group: whether each child follows the csg's material
+    left_inherits: bool,
+    right_inherits: bool,
+}
+
+impl ShapeProperties for Csg {
+    fn transform(&self) -> Transform {
+        self.transform.clone()
+    }
+
+    fn set_transform(&mut self, t: Transform) {
+        self.transform = t;
+        self.update_children();
+    }
+
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+        self.update_children();
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        panic!("a csg has no surface, normal_at must be called on one of its children");
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        panic!("a csg has no surface, tangents_at must be called on one of its children");
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let local_ray = self.transform.inverse() * ray;
+        let mut xs = self.left.intersect(&local_ray);
+        xs.append(&mut self.right.intersect(&local_ray));
+        xs.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Equal));
+        self.filter_intersections(xs)
+    }
+
+    fn includes(&self, shape: &dyn ShapeProperties) -> bool {
+        self.left.includes(shape) || self.right.includes(shape)
+    }
+
+    // the union of both children, tight enough for every operation
+    fn local_bounds(&self) -> BoundingBox {
+        let mut b = self.left.bounds();
+        b.merge(&self.right.bounds());
+        b
+    }
+
+    fn build_bvh(&mut self) {
+        self.left.build_bvh();
+        self.right.build_bvh();
+    }
+
+    fn as_trait(&self) -> &dyn ShapeProperties {
+        self
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+
+    fn set_material(&mut self, m: Material) {
+        if self.left_inherits {
+            self.left.set_material(m.clone());
+        }
+        if self.right_inherits {
+            self.right.set_material(m.clone());
+        }
+        self.material = m;
+    }
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Box<dyn ShapeProperties>, right: Box<dyn ShapeProperties>) -> Self {
+        let mut c = Csg {
+            operation,
+            material: Material::default(),
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            left,
+            right,
+            left_inherits: true,
+            right_inherits: true,
+        };
+        c.update_children();
+        c
+    }
+
+    // children marked here keep their own material when the csg is painted
+    pub fn keep_child_materials(&mut self, left: bool, right: bool) {
+        self.left_inherits = !left;
+        self.right_inherits = !right;
+    }
+
+    // xs must be sorted by t
+    pub fn filter_intersections<'a>(&self, xs: Vec<Intersection<'a>>) -> Vec<Intersection<'a>> {
+        let mut inl = false;
+        let mut inr = false;
+        let mut result: Vec<Intersection> = vec![];
+        for i in xs {
+            let lhit = self.left.includes(i.object);
+            if intersection_allowed(self.operation, lhit, inl, inr) {
+                result.push(i.clone());
+            }
+            if lhit {
+                inl = !inl;
+            } else {
+                inr = !inr;
+            }
+        }
+        result
+    }
+
+    fn world_transform(&self) -> Transform {
+        self.parent_transform.clone() * self.transform.clone()
+    }
+
+    fn update_children(&mut self) {
+        let world_transform = self.world_transform();
+        self.left.set_parent_transform(world_transform.clone());
+        self.right.set_parent_transform(world_transform);
+    }
+}
+
+#[cfg(test)]
+pub fn csg(operation: Operation, left: Box<dyn ShapeProperties>, right: Box<dyn ShapeProperties>) -> Csg {
+    Csg::new(operation, left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::sphere;
+    use crate::cube::cube;
+    use crate::group::group;
+    use crate::material::float_eq;
+    use super::Operation::{Union, Difference};
+
+    #[test]
+    fn a_csg_is_created_with_an_operation_and_two_shapes() {
+        let c = csg(Union, Box::new(sphere()), Box::new(cube()));
+        assert_eq!(c.operation, Union);
+        assert!(c.left.parent_transform() == Transform::new());
+        assert!(c.right.parent_transform() == Transform::new());
+    }
+
+    #[test]
+    fn evaluating_the_rule_for_a_csg_operation() {
+        // lhit, inl, inr, union, intersection, difference
+        let table = [
+            (true, true, true, false, true, false),
+            (true, true, false, true, false, true),
+            (true, false, true, false, true, false),
+            (true, false, false, true, false, true),
+            (false, true, true, false, true, true),
+            (false, true, false, false, true, true),
+            (false, false, true, true, false, false),
+            (false, false, false, true, false, false),
+        ];
+        for &(lhit, inl, inr, union, intersection, difference) in table.iter() {
+            assert_eq!(intersection_allowed(Union, lhit, inl, inr), union);
+            assert_eq!(intersection_allowed(Operation::Intersection, lhit, inl, inr), intersection);
+            assert_eq!(intersection_allowed(Difference, lhit, inl, inr), difference);
+        }
+    }
+
+    #[test]
+    fn filtering_a_list_of_intersections() {
+        let s1 = sphere();
+        let s2 = cube();
+        for &(op, x0, x1) in [(Union, 0, 3), (Operation::Intersection, 1, 2), (Difference, 0, 1)].iter() {
+            let c = csg(op, Box::new(s1.clone()), Box::new(s2.clone()));
+            let xs = vec![
+                Intersection::new(1.0, c.left.as_trait()),
+                Intersection::new(2.0, c.right.as_trait()),
+                Intersection::new(3.0, c.left.as_trait()),
+                Intersection::new(4.0, c.right.as_trait()),
+            ];
+            let result = c.filter_intersections(xs.clone());
+            assert_eq!(result.len(), 2);
+            assert!(float_eq(result[0].t, xs[x0].t));
+            assert!(float_eq(result[1].t, xs[x1].t));
+        }
+    }
+
+    #[test]
+    fn a_ray_misses_a_csg_object() {
+        let c = csg(Union, Box::new(sphere()), Box::new(cube()));
+        let r = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(c.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_hits_a_csg_object() {
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.set_transform(Transform::new().translate(0.0, 0.0, 0.5));
+        let c = csg(Union, Box::new(s1), Box::new(s2));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = c.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(float_eq(xs[0].t, 4.0));
+        assert!(c.left.includes(xs[0].object));
+        assert!(float_eq(xs[1].t, 6.5));
+        assert!(c.right.includes(xs[1].object));
+    }
+
+    #[test]
+    fn hits_inside_nested_groups_belong_to_their_side() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        let mut drill = cube();
+        drill.set_transform(Transform::new().scaling(0.5, 0.5, 2.0));
+        let c = csg(Difference, Box::new(g), Box::new(drill));
+
+        // through the drilled hole there is nothing left
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(c.intersect(&r).is_empty());
+        // across it the ray meets the sphere, both walls of the hole, then the sphere again
+        let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let xs = c.intersect(&r);
+        assert_eq!(xs.len(), 4);
+        assert!(c.left.includes(xs[0].object) && c.left.includes(xs[3].object));
+        assert!(c.right.includes(xs[1].object) && c.right.includes(xs[2].object));
+        assert!(float_eq(xs[1].t, 4.5) && float_eq(xs[2].t, 5.5));
+    }
+
+    #[test]
+    fn transforming_a_csg_moves_its_children() {
+        let mut c = csg(Operation::Intersection, Box::new(sphere()), Box::new(cube()));
+        c.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+        let r = Ray::new(Point::new(5.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = c.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(xs[0].object.normal_at(Point::new(5.0, 0.0, -1.0)) == Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn a_csg_material_only_paints_children_without_their_own() {
+        let red = Material::new(Tuple::new(1.0, 0.0, 0.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let blue = Material::new(Tuple::new(0.0, 0.0, 1.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let mut painted = sphere();
+        painted.set_material(red.clone());
+        let mut c = csg(Difference, Box::new(painted), Box::new(cube()));
+        c.keep_child_materials(true, false);
+        c.set_material(blue.clone());
+        assert!(c.left.material() == red);
+        assert!(c.right.material() == blue);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::*;
 use crate::shape_props::{ShapeProperties, check_axis};
+use crate::bounds::BoundingBox;
 use crate::intersection::Intersection;
 
 
@@ -7,6 +8,7 @@ use crate::intersection::Intersection;
 pub struct Cube {
     pub material: Material,
     pub transform: Transform,
+    pub parent_transform: Transform,
 }
 
 impl ShapeProperties for Cube {
@@ -18,8 +20,16 @@ impl ShapeProperties for Cube {
         self.transform = t;
     }
 
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
     fn normal_at(&self, p: Point) -> Vector {
-        let point_obj_space = self.transform.inverse() * p;
+        let point_obj_space = self.world_to_object(p);
         let local_point = point_obj_space;
         let abs_x = f64::abs(local_point.x());
         let abs_y = f64::abs(local_point.y());
@@ -33,9 +43,25 @@ impl ShapeProperties for Cube {
         } else {
             normal_obj_space= Vector::new(0.0, 0.0, local_point.z())
         }
-        let mut world_normal = self.transform.inverse().transpose() * normal_obj_space;
-        world_normal.data.w = 0.0;
-        return world_normal.normalize();
+        self.normal_to_world(normal_obj_space)
+    }
+
+    // follows the face layout of the cube map pattern
+    fn tangents_at(&self, p: Point) -> (Vector, Vector) {
+        let local_point = self.world_to_object(p);
+        let abs_x = f64::abs(local_point.x());
+        let abs_y = f64::abs(local_point.y());
+        let abs_z = f64::abs(local_point.z());
+        let maxc = f64::max(abs_x, f64::max(abs_y, abs_z));
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let (u, v) = if maxc == abs_x {
+            (Vector::new(0.0, 0.0, -local_point.x().signum()), up)
+        } else if maxc == abs_y {
+            (Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 0.0, -local_point.y().signum()))
+        } else {
+            (Vector::new(local_point.z().signum(), 0.0, 0.0), up)
+        };
+        (self.vector_to_world(u), self.vector_to_world(v))
     }
 
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
@@ -51,18 +77,16 @@ impl ShapeProperties for Cube {
             vec![]
         } else {
             vec![
-                Intersection {
-                    t: t_min,
-                    object: self.as_trait()
-                },
-                Intersection {
-                    t: t_max,
-                    object: self.as_trait()
-                }
+                Intersection::new(t_min, self.as_trait()),
+                Intersection::new(t_max, self.as_trait())
             ]
         }
     }
 
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+
     fn as_trait(&self) -> &dyn ShapeProperties {
         self
     }
@@ -80,6 +104,7 @@ impl Cube {
     pub fn default() -> Self {
         Cube {
             transform: Transform::new(),
+            parent_transform: Transform::new(),
             material: Material::default()
         }
     }
@@ -125,4 +150,20 @@ mod tests {
         assert!(n1 == Vector::new(1.0, 0.0, 0.0));
         assert!(n2 == Vector::new(-1.0, 0.0, 0.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn the_tangents_of_a_cube_follow_its_face_layout() {
+        let c = cube();
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let (u, v) = c.tangents_at(Point::new(-1.0, 0.5, -0.25));
+        assert!(u == Vector::new(0.0, 0.0, 1.0) && v == up);
+        let (u, v) = c.tangents_at(Point::new(0.3, 0.5, 1.0));
+        assert!(u == Vector::new(1.0, 0.0, 0.0) && v == up);
+        let (u, v) = c.tangents_at(Point::new(1.0, -0.5, 0.5));
+        assert!(u == Vector::new(0.0, 0.0, -1.0) && v == up);
+        let (u, v) = c.tangents_at(Point::new(-0.5, 1.0, -0.5));
+        assert!(u == Vector::new(1.0, 0.0, 0.0) && v == Vector::new(0.0, 0.0, -1.0));
+        let (u, v) = c.tangents_at(Point::new(0.5, -1.0, -0.5));
+        assert!(u == Vector::new(1.0, 0.0, 0.0) && v == Vector::new(0.0, 0.0, 1.0));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::*;
 use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
 use crate::intersection::Intersection;
-use std::rc::Rc;
 use std::mem::swap;
 
 
@@ -9,6 +9,7 @@ use std::mem::swap;
 pub struct Cylinder {
     pub material: Material,
     pub transform: Transform,
+    pub parent_transform: Transform,
     pub cylinder_minimum: f64,
     pub cylinder_maximum: f64,
     pub cylinder_closed: bool,
@@ -23,8 +24,16 @@ impl ShapeProperties for Cylinder {
         self.transform = t;
     }
 
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
     fn normal_at(&self, p: Point) -> Vector {
-        let local_point = self.transform.inverse() * p;
+        let local_point = self.world_to_object(p);
         let mut normal_obj_space: Vector;
         let max = self.cylinder_maximum;
         let min = self.cylinder_minimum;
@@ -38,9 +47,13 @@ impl ShapeProperties for Cylinder {
             normal_obj_space =  Vector::new(local_point.x(), 0.0, local_point.z());
         }
 
-        let mut world_normal = self.transform.inverse().transpose() * normal_obj_space;
-        world_normal.data.w = 0.0;
-        return world_normal.normalize();
+        self.normal_to_world(normal_obj_space)
+    }
+
+    // the caps reuse the side's frame, seen from above
+    fn tangents_at(&self, p: Point) -> (Vector, Vector) {
+        let (u, v) = shape_props::tangents_around_y(self.world_to_object(p));
+        (self.vector_to_world(u), self.vector_to_world(v))
     }
 
     fn intersect(&self, ray_world: &Ray) -> Vec<Intersection> {
@@ -55,12 +68,12 @@ impl ShapeProperties for Cylinder {
         if closed && f64::abs(ray_obj.direction().y()) > 0.0001 {
             let t = (min - ray_obj.origin().y()) / ray_obj.direction().y();
             if shape_props::check_cap(&ray_obj, t) {
-                result.push(Intersection { t, object: self.as_trait() });
+                result.push(Intersection::new(t, self.as_trait()));
             }
 
             let t = (max - ray_obj.origin().y()) / ray_obj.direction().y();
             if shape_props::check_cap(&ray_obj, t) {
-                result.push(Intersection {t, object: self.as_trait()});
+                result.push(Intersection::new(t, self.as_trait()));
             }
         }
 
@@ -88,18 +101,25 @@ impl ShapeProperties for Cylinder {
             }
             let y0 = t0 * ray_obj.direction().y() + ray_obj.origin().y();
             if y0 > min && y0 < max {
-                result.push(Intersection { t: t0, object: self.as_trait() });
+                result.push(Intersection::new(t0, self.as_trait()));
             }
 
             let y1 = t1 * ray_obj.direction().y() + ray_obj.origin().y();
             if y1 > min && y1 < max {
-                result.push(Intersection { t: t1, object: self.as_trait() });
+                result.push(Intersection::new(t1, self.as_trait()));
             }
 
             return result;
         }
     }
 
+    fn local_bounds(&self) -> BoundingBox {
+        // f64::MIN / f64::MAX mean untruncated, treat them as infinite
+        let min = if self.cylinder_minimum == f64::MIN { f64::NEG_INFINITY } else { self.cylinder_minimum };
+        let max = if self.cylinder_maximum == f64::MAX { f64::INFINITY } else { self.cylinder_maximum };
+        BoundingBox::new(Point::new(-1.0, min, -1.0), Point::new(1.0, max, 1.0))
+    }
+
     fn as_trait(&self) -> &dyn ShapeProperties {
         self
     }
@@ -117,6 +137,7 @@ impl Cylinder {
     pub fn new() -> Self {
         Cylinder {
             transform: Transform::new(),
+            parent_transform: Transform::new(),
             material: Material::default(),
             cylinder_maximum: f64::MAX,
             cylinder_minimum: f64::MIN,
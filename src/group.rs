@@ -0,0 +1,352 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::intersection::Intersection;
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use std::cmp::Ordering::Equal;
+
+// a group owns its children and is never hit itself: intersect transforms the
+// ray into group space and hands it down, so every hit refers to a leaf shape
+pub struct Group {
+    // none until the group is painted, so children keep whatever they were built with
+    material: Option<Material>,
+    pub transform: Transform,
+    pub parent_transform: Transform,
+    // private so every change goes through a method that drops the bvh
+    children: Vec<Box<dyn ShapeProperties>>,
+    // per child: whether it follows the group's material
+    inherits: Vec<bool>,
+    // built on demand by build_bvh, dropped whenever a child is added or changed
+    bvh: Option<Bvh>,
+}
+
+impl ShapeProperties for Group {
+    fn transform(&self) -> Transform {
+        self.transform.clone()
+    }
+
+    fn set_transform(&mut self, t: Transform) {
+        self.transform = t;
+        self.update_children();
+    }
+
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+        self.update_children();
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        panic!("a group has no surface, normal_at must be called on one of its children");
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        panic!("a group has no surface, tangents_at must be called on one of its children");
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let local_ray = self.transform.inverse() * ray;
+        let mut result: Vec<Intersection> = match &self.bvh {
+            Some(bvh) => bvh.intersect(&self.children, &local_ray),
+            None => {
+                let mut xs: Vec<Intersection> = vec![];
+                for child in &self.children {
+                    xs.append(&mut child.intersect(&local_ray));
+                }
+                xs
+            }
+        };
+        result.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Equal));
+        result
+    }
+
+    fn includes(&self, shape: &dyn ShapeProperties) -> bool {
+        self.children.iter().any(|child| child.includes(shape))
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        if let Some(bvh) = &self.bvh {
+            return bvh.bounds();
+        }
+        let mut b = BoundingBox::empty();
+        for child in &self.children {
+            b.merge(&child.bounds());
+        }
+        b
+    }
+
+    fn build_bvh(&mut self) {
+        for child in &mut self.children {
+            child.build_bvh();
+        }
+        self.bvh = Some(Bvh::build(&self.children));
+    }
+
+    fn as_trait(&self) -> &dyn ShapeProperties {
+        self
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone().unwrap_or_else(Material::default)
+    }
+
+    // the material is pushed down so a whole sub-assembly can be painted at once,
+    // children added with add_child_keeping_material are left alone
+    fn set_material(&mut self, m: Material) {
+        for (child, inherits) in self.children.iter_mut().zip(&self.inherits) {
+            if *inherits {
+                child.set_material(m.clone());
+            }
+        }
+        self.material = Some(m);
+    }
+}
+
+impl Group {
+    pub fn new() -> Self {
+        Group {
+            material: None,
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            children: vec![],
+            inherits: vec![],
+            bvh: None
+        }
+    }
+
+    // the child follows the group's material, now and whenever it changes
+    pub fn add_child(&mut self, mut child: Box<dyn ShapeProperties>) {
+        if let Some(m) = &self.material {
+            child.set_material(m.clone());
+        }
+        self.push_child(child, true);
+    }
+
+    // the child keeps its own material whatever the group is painted with
+    pub fn add_child_keeping_material(&mut self, child: Box<dyn ShapeProperties>) {
+        self.push_child(child, false);
+    }
+
+    fn push_child(&mut self, mut child: Box<dyn ShapeProperties>, inherits: bool) {
+        child.set_parent_transform(self.world_transform());
+        self.children.push(child);
+        self.inherits.push(inherits);
+        self.bvh = None;
+    }
+
+    pub fn children(&self) -> &[Box<dyn ShapeProperties>] {
+        &self.children
+    }
+
+    // for moving or repainting children in place, drops the bvh so it has to be
+    // built again; new children go through add_child to pick up the group's transform
+    pub fn children_mut(&mut self) -> &mut [Box<dyn ShapeProperties>] {
+        self.bvh = None;
+        &mut self.children
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn world_transform(&self) -> Transform {
+        self.parent_transform.clone() * self.transform.clone()
+    }
+
+    fn update_children(&mut self) {
+        let world_transform = self.world_transform();
+        for child in &mut self.children {
+            child.set_parent_transform(world_transform.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn group() -> Group {
+    Group::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::sphere;
+    use crate::material::float_eq;
+
+    #[test]
+    fn creating_a_new_group() {
+        let g = group();
+        assert!(g.transform() == Transform::new());
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    fn adding_a_child_to_a_group() {
+        let mut g = group();
+        g.set_transform(Transform::new().translate(1.0, 0.0, 0.0));
+        g.add_child(Box::new(sphere()));
+        assert_eq!(g.len(), 1);
+        assert!(g.children()[0].parent_transform() == Transform::new().translate(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_an_empty_group() {
+        let g = group();
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 0);
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_a_nonempty_group() {
+        let mut g = group();
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.set_transform(Transform::new().translate(0.0, 0.0, -3.0));
+        let mut s3 = sphere();
+        s3.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+
+        g.add_child(Box::new(s1));
+        g.add_child(Box::new(s2));
+        g.add_child(Box::new(s3));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 4);
+        assert!(float_eq(xs[0].t, 1.0));
+        assert!(float_eq(xs[1].t, 3.0));
+        assert!(float_eq(xs[2].t, 4.0));
+        assert!(float_eq(xs[3].t, 6.0));
+    }
+
+    #[test]
+    fn intersecting_a_transformed_group() {
+        let mut g = group();
+        g.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
+        let mut s = sphere();
+        s.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+        g.add_child(Box::new(s));
+
+        let r = Ray::new(Point::new(10.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 2);
+    }
+
+    #[test]
+    fn a_group_material_only_paints_children_without_their_own() {
+        let red = Material::new(Tuple::new(1.0, 0.0, 0.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let blue = Material::new(Tuple::new(0.0, 0.0, 1.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let green = Material::new(Tuple::new(0.0, 1.0, 0.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        let mut painted = sphere();
+        painted.set_material(red.clone());
+        g.add_child_keeping_material(Box::new(painted));
+        let mut inner = group();
+        inner.add_child(Box::new(sphere()));
+        g.add_child(Box::new(inner));
+
+        g.set_material(blue.clone());
+        assert!(g.children()[0].material() == blue);
+        assert!(g.children()[1].material() == red);
+        assert!(g.children()[2].material() == blue);
+
+        // children that took the group's material follow it when it changes
+        g.set_material(green.clone());
+        assert!(g.children()[0].material() == green);
+        assert!(g.children()[1].material() == red);
+        assert!(g.children()[2].material() == green);
+
+        // a child added later picks up the group's current material
+        g.add_child(Box::new(sphere()));
+        assert!(g.children()[3].material() == green);
+    }
+
+    #[test]
+    fn a_kept_material_survives_matching_the_group_material() {
+        let blue = Material::new(Tuple::new(0.0, 0.0, 1.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let green = Material::new(Tuple::new(0.0, 1.0, 0.0, 0.0), 0.1, 0.9, 0.9, 200.0);
+        let mut g = group();
+        // a default material set on purpose is still the child's own
+        g.add_child_keeping_material(Box::new(sphere()));
+        g.set_material(blue);
+        g.set_material(green);
+        assert!(g.children()[0].material() == Material::default());
+    }
+
+    #[test]
+    fn intersecting_a_group_through_its_bvh() {
+        let mut g = group();
+        g.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
+        for i in 0..10 {
+            let mut s = sphere();
+            s.set_transform(Transform::new().translate(0.0, 3.0 * i as f64, 0.0));
+            g.add_child(Box::new(s));
+        }
+        g.build_bvh();
+
+        let r = Ray::new(Point::new(0.0, 18.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(float_eq(xs[0].t, 8.0));
+        assert!(float_eq(xs[1].t, 12.0));
+
+        g.add_child(Box::new(sphere()));
+        let r = Ray::new(Point::new(0.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 4);
+    }
+
+    #[test]
+    fn moving_a_child_in_place_drops_the_bvh() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        g.build_bvh();
+        g.children_mut()[0].set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+        let r = Ray::new(Point::new(5.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn converting_a_point_from_world_to_object_space() {
+        let mut g1 = group();
+        g1.set_transform(Transform::new().rotate_y(PI / 2.0));
+        let mut g2 = group();
+        g2.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
+        let mut s = sphere();
+        s.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+        g2.add_child(Box::new(s));
+        g1.add_child(Box::new(g2));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -20.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g1.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        let p = xs[0].object.world_to_object(Point::new(-2.0, 0.0, -10.0));
+        assert!(p == Point::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn finding_the_normal_on_a_child_object() {
+        let mut g1 = group();
+        g1.set_transform(Transform::new().rotate_y(PI / 2.0));
+        let mut g2 = group();
+        g2.set_transform(Transform::new().scaling(1.0, 2.0, 3.0));
+        let mut s = sphere();
+        s.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
+        g2.add_child(Box::new(s));
+        g1.add_child(Box::new(g2));
+
+        let r = Ray::new(Point::new(0.0, 0.0, -20.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = g1.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        let n = xs[0].object.normal_at(Point::new(1.7321, 1.1547, -5.5774));
+        assert!(n == Vector::new(0.2857, 0.4286, -0.8571));
+    }
+}
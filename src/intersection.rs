@@ -2,11 +2,31 @@
 use crate::tuple::{VectorProperties, Point, Vector};
 use crate::ray::{Ray};
 use crate::shape_props::ShapeProperties;
+use crate::material::MaterialProperties;
 
 #[derive(Clone)]
 pub struct Intersection<'a> {
     pub t: f64,
-    pub object: &'a dyn ShapeProperties
+    pub object: &'a dyn ShapeProperties,
+    // barycentric coordinates of the hit, only meaningful for triangles
+    pub u: f64,
+    pub v: f64,
+    // which triangle of a mesh was hit, 0 for every other shape
+    pub index: usize
+}
+
+impl<'a> Intersection<'a> {
+    pub fn new(t: f64, object: &'a dyn ShapeProperties) -> Intersection<'a> {
+        Intersection { t, object, u: 0.0, v: 0.0, index: 0 }
+    }
+
+    pub fn new_with_uv(t: f64, object: &'a dyn ShapeProperties, u: f64, v: f64) -> Intersection<'a> {
+        Intersection { t, object, u, v, index: 0 }
+    }
+
+    pub fn new_with_index(t: f64, object: &'a dyn ShapeProperties, index: usize, u: f64, v: f64) -> Intersection<'a> {
+        Intersection { t, object, u, v, index }
+    }
 }
 
 impl PartialEq for Intersection<'_> {
@@ -23,14 +43,24 @@ pub struct AugIntersection<'a> {
     pub object:&'a dyn ShapeProperties,
     pub point: Point,
     pub eyev: Vector,
+    // the geometric normal turned towards the eye, over_point, under_point and
+    // refraction are based on it
     pub normalv: Vector,
+    // normalv tilted by bump noise and normal maps, used for lighting and reflectv;
+    // never faces away from the eye
+    pub shading_normalv: Vector,
     pub inside: bool,
     pub over_point: Point,
     // the reflected ray direction
     pub reflectv: Vector,
     pub n1: f64,
     pub n2: f64,
-    pub under_point: Point
+    pub under_point: Point,
+    // the object the ray travelled through to get here, none in open space
+    pub medium: Option<&'a dyn ShapeProperties>,
+    // of the ray, carried on by the rays spawned here
+    pub wavelength: Option<f64>,
+    pub scattered: bool
 }
 
 impl PartialEq for AugIntersection<'_> {
@@ -41,6 +71,7 @@ impl PartialEq for AugIntersection<'_> {
             && self.point == other.point
             && self.eyev == other.eyev
         && self.normalv == other.normalv
+        && self.shading_normalv == other.shading_normalv
         && self.inside == other.inside
         && self.over_point == other.over_point
         && self.reflectv == other.reflectv
@@ -75,10 +106,12 @@ pub fn prepare_computations<'a>(hit: &'a Intersection, r: &'a Ray, xs: &'a Vec<I
 
     let mut n1 = 1.0;
     let mut n2 = 1.0;
+    let mut medium = None;
     for item in xs {
         if item == hit {
             if containers.len() != 0 {
-                n1 = containers.last().unwrap().material().refractive_index;
+                n1 = containers.last().unwrap().material().refractive_index_at(r.wavelength());
+                medium = containers.last().copied();
             }
         }
 
@@ -95,7 +128,7 @@ pub fn prepare_computations<'a>(hit: &'a Intersection, r: &'a Ray, xs: &'a Vec<I
 
         if item == hit {
             if containers.len() != 0 {
-                n2 = containers.last().unwrap().material().refractive_index;
+                n2 = containers.last().unwrap().material().refractive_index_at(r.wavelength());
             }
 
             break;
@@ -103,17 +136,29 @@ pub fn prepare_computations<'a>(hit: &'a Intersection, r: &'a Ray, xs: &'a Vec<I
     }
 
     let point = r.position_at(hit.t);
-    let mut normalv = hit.object.normal_at(point);
+    let mut normalv = hit.object.normal_at_hit(point, hit);
+    let mut shading_normalv = hit.object.material().shading_normal(hit, point, normalv);
     let eyev =  r.direction() * (-1.0);
     let mut inside: bool = false;
     // obtuse angle
     if eyev.dot(normalv) < 0.0 {
         inside = true;
         normalv = -1.0 * normalv;
+        shading_normalv = -1.0 * shading_normalv;
+    }
+    // a strong bump can tilt the shading normal past the eye, bend it back just enough
+    let facing = shading_normalv.dot(eyev);
+    if facing < EPSILON {
+        shading_normalv = (shading_normalv + eyev * (EPSILON - facing)).normalize();
     }
     let over_point = point + normalv * EPSILON;
     let under_point = point - normalv * EPSILON;
-    let reflectv = r.direction().reflect(normalv);
+    // and a reflection off it may head into the surface, fold it back out
+    let mut reflectv = r.direction().reflect(shading_normalv);
+    let below = reflectv.dot(normalv);
+    if below < 0.0 {
+        reflectv = reflectv - normalv * (2.0 * below);
+    }
 
     AugIntersection {
         t: hit.t,
@@ -121,15 +166,20 @@ pub fn prepare_computations<'a>(hit: &'a Intersection, r: &'a Ray, xs: &'a Vec<I
         point,
         eyev,
         normalv,
+        shading_normalv,
         inside,
         over_point,
         reflectv,
         n1,
         n2,
-        under_point
+        under_point,
+        medium,
+        wavelength: r.wavelength(),
+        scattered: r.scattered()
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
     use crate::tuple::{Point, PointProperties, Vector, VectorProperties};
@@ -138,12 +188,17 @@ mod tests {
     use crate::sphere::sphere;
     use crate::sphere;
     use crate::plane;
+    use crate::material::{Material, NormalMap, Dispersion};
+    use crate::tuple::Tuple;
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+    use crate::pattern::{Texture, UvMapping, gradient_pattern};
+    use crate::canvas::{Canvas, CanvasProperties};
 
     #[test]
     fn intersection_on_outside() {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = sphere();
-        let i = Intersection {t: 4.0, object: &shape};
+        let i = Intersection::new(4.0, &shape);
         let vec = vec![i.clone()];
         let comps = prepare_computations(&i, &r, &vec);
         assert_eq!(comps.inside, false);
@@ -153,7 +208,7 @@ mod tests {
     fn intersection_inside() {
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let shape = sphere();
-        let i = Intersection {t: 1.0, object: &shape};
+        let i = Intersection::new(1.0, &shape);
         let vec = vec![i.clone()];
         let comps = prepare_computations(&i, &r, &vec);
         // let true_p = Vector::new(0.0, 0.0, 1.0);
@@ -166,12 +221,105 @@ mod tests {
     fn test_precomputing_the_reflection_vector() {
         let shape = plane::plane();
         let r = Ray::new(Point::new(0.0, 1.0, -1.0), Vector::new(0.0, - f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0));
-        let i = Intersection { t: f64::sqrt(2.0), object: &shape };
+        let i = Intersection::new(f64::sqrt(2.0), &shape);
         let vec = vec![i.clone()];
         let comps = prepare_computations(&i, &r, &vec);
         assert_eq!(comps.reflectv == Vector::new(0.0, f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0), true);
     }
 
+    #[test]
+    fn bump_noise_tilts_the_normal() {
+        let r = Ray::new(Point::new(0.3, 1.0, 0.7), Vector::new(0.0, -1.0, 0.0));
+        let mut shape = plane::plane();
+        let i = Intersection::new(1.0, &shape);
+        let xs = vec![i.clone()];
+        assert!(prepare_computations(&i, &r, &xs).shading_normalv == Vector::new(0.0, 1.0, 0.0));
+
+        shape.material.bump = 0.5;
+        shape.material.bump_scale = 0.25;
+        let i = Intersection::new(1.0, &shape);
+        let xs = vec![i.clone()];
+        let comps = prepare_computations(&i, &r, &xs);
+        assert!(comps.shading_normalv != Vector::new(0.0, 1.0, 0.0));
+        assert!(float_eq(comps.shading_normalv.mag(), 1.0));
+        // the point still lies on the surface, only the shading normal changes
+        assert!(comps.point == Point::new(0.3, 0.0, 0.7));
+        assert!(comps.normalv == Vector::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_strong_bump_at_a_grazing_angle_keeps_the_hit_outside() {
+        let mut shape = sphere();
+        shape.material.bump = 3.0;
+        shape.material.bump_scale = 0.05;
+        let mut tilted_away = 0;
+        for k in 0..50 {
+            // rays skimming the right edge of the sphere
+            let y = -0.5 + k as f64 * 0.02;
+            let x = f64::sqrt(1.0 - y * y) - 0.002;
+            let r = Ray::new(Point::new(x, y, -5.0), Vector::new(0.0, 0.0, 1.0));
+            let xs = shape.intersect(&r);
+            let comps = prepare_computations(&xs[0], &r, &xs);
+            let bumped = shape.material.shading_normal(&xs[0], comps.point, shape.normal_at(comps.point));
+            if bumped.dot(comps.eyev) < 0.0 {
+                tilted_away += 1;
+            }
+
+            assert!(!comps.inside);
+            assert!(float_eq(comps.n1, 1.0) && float_eq(comps.n2, 1.0));
+            assert!(comps.over_point.x() * comps.over_point.x() + comps.over_point.y() * comps.over_point.y()
+                + comps.over_point.z() * comps.over_point.z() > 1.0);
+            assert!(comps.shading_normalv.dot(comps.eyev) > 0.0);
+            assert!(comps.reflectv.dot(comps.normalv) >= 0.0);
+        }
+        // the bump really does tilt some normals away from the eye
+        assert!(tilted_away > 0);
+    }
+
+    fn flat_hit_normal(material: Material) -> Vector {
+        let mut shape = plane::plane();
+        shape.material = material;
+        let r = Ray::new(Point::new(0.3, 1.0, 0.7), Vector::new(0.0, -1.0, 0.0));
+        let i = Intersection::new(1.0, &shape);
+        let xs = vec![i.clone()];
+        prepare_computations(&i, &r, &xs).shading_normalv
+    }
+
+    fn normal_image(color: Tuple) -> Texture {
+        let mut image = Canvas::new(1, 1);
+        image.write_pixel(0, 0, color);
+        Texture::new(image)
+    }
+
+    #[test]
+    fn a_normal_map_is_read_in_tangent_space() {
+        let mut m = Material::default();
+        m.normal_map = Some(NormalMap::Image(normal_image(Tuple::new(0.5, 0.5, 1.0, 0.0)), UvMapping::Planar));
+        assert!(flat_hit_normal(m.clone()) == Vector::new(0.0, 1.0, 0.0));
+
+        // red leans toward the u tangent, green toward v
+        m.normal_map = Some(NormalMap::Image(normal_image(Tuple::new(1.0, 0.5, 1.0, 0.0)), UvMapping::Planar));
+        assert!(flat_hit_normal(m.clone()) == Vector::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0));
+        m.normal_map = Some(NormalMap::Image(normal_image(Tuple::new(0.5, 1.0, 1.0, 0.0)), UvMapping::Planar));
+        assert!(flat_hit_normal(m.clone()) == Vector::new(0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn a_bump_map_leans_the_normal_down_the_slope() {
+        let black = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        let white = Tuple::new(1.0, 1.0, 1.0, 0.0);
+        let mut m = Material::default();
+        m.normal_map = Some(NormalMap::Bump(gradient_pattern(black, white), 1.0));
+        // the height climbs by 1 per unit along x
+        assert!(flat_hit_normal(m.clone()) == Vector::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0));
+
+        let mut heights = gradient_pattern(black, white);
+        heights.set_transform(&Transform::new().rotate_y(-PI / 2.0));
+        m.normal_map = Some(NormalMap::Bump(heights, 0.5));
+        let n = flat_hit_normal(m.clone());
+        assert!(n == Vector::new(0.0, 0.89443, -0.44721));
+    }
+
     #[test]
     fn test_finding_n1_and_n2_at_various_intersections() {
         let mut a = sphere::glass_sphere();
@@ -186,12 +334,12 @@ mod tests {
 
         let r = Ray::new(Point::new(0.0, 0.0, -4.0), Vector::new(0.0, 0.0, 1.0));
         let mut xs: Vec<Intersection> = vec![];
-        xs.push(Intersection{t: 2.0, object: &a});
-        xs.push(Intersection{t: 2.75, object: &b});
-        xs.push(Intersection{t: 3.25, object: &c});
-        xs.push(Intersection{t: 4.75, object: &b});
-        xs.push(Intersection{t: 5.25, object: &c});
-        xs.push(Intersection{t: 6.0, object: &a});
+        xs.push(Intersection::new(2.0, &a));
+        xs.push(Intersection::new(2.75, &b));
+        xs.push(Intersection::new(3.25, &c));
+        xs.push(Intersection::new(4.75, &b));
+        xs.push(Intersection::new(5.25, &c));
+        xs.push(Intersection::new(6.0, &a));
 
         let comps0 = prepare_computations(&xs[0], &r, &xs);
         assert_eq!(float_eq(comps0.n1, 1.0), true);
@@ -212,6 +360,29 @@ mod tests {
         let comps4 = prepare_computations(&xs[4], &r, &xs);
         assert_eq!(float_eq(comps4.n1, 2.5), true);
         assert_eq!(float_eq(comps4.n2, 1.5), true);
+
+        // the medium is where n1 comes from
+        assert!(comps0.medium.is_none());
+        assert!(comps1.medium.unwrap().includes(&a));
+        assert!(comps3.medium.unwrap().includes(&c));
+        assert!(comps4.medium.unwrap().includes(&c));
+    }
+
+    #[test]
+    fn a_dispersive_material_bends_blue_more_than_red() {
+        let mut glass = sphere::glass_sphere();
+        glass.material.dispersion = Some(Dispersion::Cauchy(1.5, 0.01));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = vec![Intersection::new(4.0, &glass), Intersection::new(6.0, &glass)];
+
+        let white = prepare_computations(&xs[0], &r, &xs);
+        assert!(float_eq(white.n2, 1.5) && white.wavelength.is_none());
+        let blue_ray = r.with_wavelength(Some(400.0));
+        let blue = prepare_computations(&xs[0], &blue_ray, &xs);
+        assert!(float_eq(blue.n2, 1.5625) && blue.wavelength == Some(400.0));
+        let red_ray = r.with_wavelength(Some(700.0));
+        let red = prepare_computations(&xs[1], &red_ray, &xs);
+        assert!(float_eq(red.n1, 1.5 + 0.01 / 0.49) && float_eq(red.n2, 1.0));
     }
 
     #[test]
@@ -219,7 +390,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let mut shape = sphere::glass_sphere();
         shape.transform  = Transform::new().translate(0.0, 0.0, 1.0);
-        let i = Intersection {t: 5.0, object: &shape};
+        let i = Intersection::new(5.0, &shape);
         let xs = vec![i.clone()];
         let comps = prepare_computations(&i, &r, &xs);
         assert!(comps.under_point.z() > EPSILON / 2.0);
@@ -230,8 +401,8 @@ mod tests {
     fn test_schlink_approximation_under_total_internal_reflection() {
         let shape = sphere::glass_sphere();
         let r = Ray::new(Point::new(0.0, 0.0, f64::sqrt(2.0) / 2.0), Vector::new(0.0, 1.0, 0.0));
-        let i1 = Intersection{t: -f64::sqrt(2.0) / 2.0, object: &shape};
-        let i2 = Intersection{t: f64::sqrt(2.0) / 2.0, object: &shape};
+        let i1 = Intersection::new(-f64::sqrt(2.0) / 2.0, &shape);
+        let i2 = Intersection::new(f64::sqrt(2.0) / 2.0, &shape);
 
         let xs = vec![i1.clone(), i2.clone()];
         let comps = prepare_computations(&xs[1], &r, &xs);
@@ -244,8 +415,8 @@ mod tests {
     fn test_the_schlink_approximation_with_a_perpendicular_viewing_angle() {
         let shape = sphere::glass_sphere();
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
-        let i1 = Intersection{t: -1.0, object: &shape};
-        let i2 = Intersection{t: 1.0, object: &shape};
+        let i1 = Intersection::new(-1.0, &shape);
+        let i2 = Intersection::new(1.0, &shape);
 
         let xs = vec![i1.clone(), i2.clone()];
         let comps = prepare_computations(&xs[1], &r, &xs);
@@ -258,7 +429,7 @@ mod tests {
     fn test_the_schlick_approximation_with_small_angle() {
         let shape = sphere::glass_sphere();
         let r = Ray::new(Point::new(0.0, 0.99, -2.0), Vector::new(0.0, 0.0, 1.0));
-        let i1 = Intersection{t: 1.8589, object: &shape};
+        let i1 = Intersection::new(1.8589, &shape);
 
         let xs = vec![i1.clone()];
         let comps = prepare_computations(&xs[0], &r, &xs);
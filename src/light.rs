@@ -1,4 +1,5 @@
-use crate::tuple::{Tuple, Point};
+use crate::tuple::{Tuple, Point, Vector, VectorProperties};
+use rand::Rng;
 
 #[derive(Clone, Copy)]
 pub struct PointLight {
@@ -29,9 +30,183 @@ impl PointLightProperties for PointLight {
     }
 }
 
+// one direction a shaded point receives light from
+#[derive(Clone, Copy)]
+pub struct LightSample {
+    // unit vector from the shaded point towards the light
+    pub direction: Vector,
+    // how far a shadow ray has to travel, infinite for a directional light
+    pub distance: f64,
+    // cleared by World::light_samples when the shadow ray is blocked
+    pub visible: bool,
+}
+
+impl LightSample {
+    pub fn towards(from: Point, to: Point) -> Self {
+        let v = to - from;
+        LightSample { direction: v.normalize(), distance: v.mag(), visible: true }
+    }
+}
+
+// anything that can illuminate a surface, shading averages over the samples
+// and shadows are tested against each one
+pub trait Light: Send + Sync {
+    // named apart from PointLightProperties::intensity so both traits can be in scope
+    fn light_intensity(&self) -> Tuple;
+
+    // a point light has a single sample, an area light one per cell
+    fn samples(&self, point: Point) -> Vec<LightSample>;
+
+    // scales diffuse and specular at point, only spot lights fall off
+    fn attenuation(&self, _point: Point) -> f64 {
+        1.0
+    }
+}
+
+impl Light for PointLight {
+    fn light_intensity(&self) -> Tuple {
+        self.intensity
+    }
+
+    fn samples(&self, point: Point) -> Vec<LightSample> {
+        vec![LightSample::towards(point, self.position)]
+    }
+}
+
+// a rectangle spanned by uvec and vvec from corner, split into usteps x vsteps
+// cells that are each sampled once
+#[derive(Clone, Copy)]
+pub struct AreaLight {
+    pub corner: Point,
+    pub uvec: Vector,
+    pub usteps: usize,
+    pub vvec: Vector,
+    pub vsteps: usize,
+    pub intensity: Tuple,
+    // sample a random spot in each cell instead of its center, trades banding for noise
+    pub jitter: bool,
+}
+
+impl AreaLight {
+    pub fn new(corner: Point, full_uvec: Vector, usteps: usize, full_vvec: Vector, vsteps: usize, intensity: Tuple) -> Self {
+        let usteps = usize::max(usteps, 1);
+        let vsteps = usize::max(vsteps, 1);
+        AreaLight {
+            corner,
+            uvec: full_uvec * (1.0 / usteps as f64),
+            usteps,
+            vvec: full_vvec * (1.0 / vsteps as f64),
+            vsteps,
+            intensity,
+            jitter: false
+        }
+    }
+
+    pub fn point_on_light(&self, u: usize, v: usize) -> Point {
+        let (du, dv) = if self.jitter {
+            let mut rng = rand::thread_rng();
+            (rng.gen::<f64>(), rng.gen::<f64>())
+        } else {
+            (0.5, 0.5)
+        };
+        self.corner + self.uvec * (u as f64 + du) + self.vvec * (v as f64 + dv)
+    }
+}
+
+impl Light for AreaLight {
+    fn light_intensity(&self) -> Tuple {
+        self.intensity
+    }
+
+    fn samples(&self, point: Point) -> Vec<LightSample> {
+        let mut samples = Vec::with_capacity(self.usteps * self.vsteps);
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                samples.push(LightSample::towards(point, self.point_on_light(u, v)));
+            }
+        }
+        samples
+    }
+}
+
+// parallel rays from infinitely far away, like the sun
+#[derive(Clone, Copy)]
+pub struct DirectionalLight {
+    // the direction the light travels in
+    pub direction: Vector,
+    pub intensity: Tuple,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector, intensity: Tuple) -> Self {
+        DirectionalLight { direction: direction.normalize(), intensity }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn light_intensity(&self) -> Tuple {
+        self.intensity
+    }
+
+    fn samples(&self, _point: Point) -> Vec<LightSample> {
+        vec![LightSample { direction: self.direction * -1.0, distance: f64::INFINITY, visible: true }]
+    }
+}
+
+// a point light restricted to a cone, full strength inside inner_angle and
+// fading smoothly to nothing at outer_angle (both measured from the axis)
+#[derive(Clone, Copy)]
+pub struct SpotLight {
+    pub position: Point,
+    pub direction: Vector,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    pub intensity: Tuple,
+}
+
+impl SpotLight {
+    pub fn new(position: Point, direction: Vector, inner_angle: f64, outer_angle: f64, intensity: Tuple) -> Self {
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            inner_angle,
+            outer_angle: f64::max(outer_angle, inner_angle),
+            intensity
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn light_intensity(&self) -> Tuple {
+        self.intensity
+    }
+
+    fn samples(&self, point: Point) -> Vec<LightSample> {
+        vec![LightSample::towards(point, self.position)]
+    }
+
+    fn attenuation(&self, point: Point) -> f64 {
+        let cos_angle = (point - self.position).normalize().dot(self.direction);
+        let cos_inner = f64::cos(self.inner_angle);
+        let cos_outer = f64::cos(self.outer_angle);
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        // smoothstep between the two cone edges
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tuple::PointProperties;
+    use crate::tuple::{PointProperties, VectorProperties};
+    use crate::material::float_eq;
+    use std::f64::consts::PI;
 
 
     #[test]
@@ -45,4 +220,79 @@ mod tests {
         assert_eq!(intensity == intensity_clone, true);
         assert_eq!(position_clone == position, true);
     }
+
+    #[test]
+    fn creating_an_area_light() {
+        let light = AreaLight::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0), 4,
+            Vector::new(0.0, 0.0, 1.0), 2,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        assert!(light.uvec == Vector::new(0.5, 0.0, 0.0));
+        assert!(light.vvec == Vector::new(0.0, 0.0, 0.5));
+        assert_eq!(light.samples(Point::new(0.0, 5.0, 0.0)).len(), 8);
+    }
+
+    #[test]
+    fn finding_a_single_point_on_an_area_light() {
+        let light = AreaLight::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0), 4,
+            Vector::new(0.0, 0.0, 1.0), 2,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        assert!(light.point_on_light(0, 0) == Point::new(0.25, 0.0, 0.25));
+        assert!(light.point_on_light(1, 0) == Point::new(0.75, 0.0, 0.25));
+        assert!(light.point_on_light(2, 1) == Point::new(1.25, 0.0, 0.75));
+        assert!(light.point_on_light(3, 1) == Point::new(1.75, 0.0, 0.75));
+    }
+
+    #[test]
+    fn jittered_points_stay_inside_their_cell() {
+        let mut light = AreaLight::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0), 4,
+            Vector::new(0.0, 0.0, 1.0), 2,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        light.jitter = true;
+        for _ in 0..20 {
+            let p = light.point_on_light(2, 1);
+            assert!(p.x() >= 1.0 && p.x() <= 1.5);
+            assert!(p.z() >= 0.5 && p.z() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn a_directional_light_shines_from_infinitely_far_away() {
+        let light = DirectionalLight::new(Vector::new(0.0, -2.0, 0.0), Tuple::new(1.0, 1.0, 1.0, 1.0));
+        let samples = light.samples(Point::new(3.0, 0.0, -7.0));
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].direction == Vector::new(0.0, 1.0, 0.0));
+        assert!(samples[0].distance.is_infinite());
+        assert_eq!(light.attenuation(Point::new(100.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn a_spot_light_falls_off_between_its_cones() {
+        let light = SpotLight::new(
+            Point::new(0.0, 10.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            PI / 8.0,
+            PI / 4.0,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        assert_eq!(light.attenuation(Point::new(0.0, 0.0, 0.0)), 1.0);
+        // 45 degrees off the axis is the outer edge
+        assert!(light.attenuation(Point::new(10.0, 0.0, 0.0)) < 1e-6);
+        assert_eq!(light.attenuation(Point::new(0.0, 20.0, 0.0)), 0.0);
+
+        let halfway = light.attenuation(Point::new(10.0 * f64::tan(3.0 * PI / 16.0), 0.0, 0.0));
+        assert!(halfway > 0.0 && halfway < 1.0);
+
+        let samples = light.samples(Point::new(0.0, 0.0, 0.0));
+        assert!(samples[0].direction == Vector::new(0.0, 1.0, 0.0));
+        assert!(float_eq(samples[0].distance, 10.0));
+    }
 }
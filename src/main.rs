@@ -16,9 +16,20 @@ mod sphere;
 mod plane;
 mod cylinder;
 mod cone;
+mod group;
+mod csg;
+mod triangle;
+mod smooth_triangle;
+mod mesh;
+mod bounds;
+mod bvh;
+mod scene;
+mod noise;
+mod path_tracer;
+mod spectrum;
 
 
-use canvas::Canvas;
+use canvas::{Canvas, FileFormat, ToneMap};
 use crate::canvas::CanvasProperties;
 use crate::tuple::{Tuple, TupleProperties, Point, PointProperties, VectorProperties, Vector};
 use crate::ray::Ray;
@@ -27,10 +38,14 @@ use crate::light::{PointLight, PointLightProperties};
 use crate::material::{Material, MaterialProperties};
 use std::f64::consts::PI;
 use crate::world::{World, WorldProperties};
-use crate::camera::{Camera, CameraProperties, render};
+use crate::camera::{Camera, CameraProperties, render_parallel};
+use crate::path_tracer::{Integrator, DEFAULT_WHITTED_DEPTH, DEFAULT_PATH_DEPTH};
 use crate::pattern::gradient_pattern;
 use crate::shape_props::ShapeProperties;
-use crate::cone::Cone;
+use crate::scene::load_scene;
+use std::env;
+use std::process;
+use std::thread;
 
 // fn run_chapter_5() {
 //     let canvas_width = 100;
@@ -356,14 +371,125 @@ use crate::cone::Cone;
 //     canvas.to_ppm(file);
 // }
 
-fn run_chapter_13() {
-    let shape = cone();
+struct Options {
+    scene: String,
+    output: String,
+    width: Option<f64>,
+    height: Option<f64>,
+    samples: Option<usize>,
+    exposure: f64,
+    tone_map: ToneMap,
+    // picked from the output extension when not given
+    format: Option<FileFormat>,
+    // overrides the scene camera's integrator when given
+    integrator: Option<String>,
+    max_depth: Option<u32>,
+    threads: usize,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: raytracer --scene <file.yaml> [--output <file.png|ppm|pfm>] [--format p3|p6|png|pfm] [--width <px>] [--height <px>] [--samples <n>] [--exposure <f>] [--tone-map clamp|reinhard|aces] [--integrator whitted|path] [--max-depth <n>] [--threads <n>]");
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        scene: String::new(),
+        output: "output.png".to_string(),
+        width: None,
+        height: None,
+        samples: None,
+        exposure: 1.0,
+        tone_map: ToneMap::Clamp,
+        format: None,
+        integrator: None,
+        max_depth: None,
+        threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--scene" => options.scene = value,
+            "--output" => options.output = value,
+            "--width" => options.width = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--height" => options.height = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--samples" => options.samples = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--exposure" => options.exposure = value.parse().unwrap_or_else(|_| usage()),
+            "--tone-map" => options.tone_map = match value.as_str() {
+                "clamp" => ToneMap::Clamp,
+                "reinhard" => ToneMap::Reinhard,
+                "aces" => ToneMap::Aces,
+                _ => usage(),
+            },
+            "--format" => options.format = Some(match value.as_str() {
+                "p3" => FileFormat::PpmAscii,
+                "p6" => FileFormat::PpmBinary,
+                "png" => FileFormat::Png,
+                "pfm" => FileFormat::Pfm,
+                _ => usage(),
+            }),
+            "--integrator" => options.integrator = match value.as_str() {
+                "whitted" | "path" => Some(value),
+                _ => usage(),
+            },
+            "--max-depth" => options.max_depth = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--threads" => options.threads = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
 
+    if options.scene.is_empty() {
+        usage();
+    }
+    options
 }
 
 fn main() {
-    println!("Started!");
-    // run_chapter_6();
-    run_chapter_9();
-    println!("Finished!");
+    let options = parse_args();
+    let scene = match load_scene(&options.scene) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("{}: {}", options.scene, e);
+            process::exit(1);
+        }
+    };
+
+    // a width or height given on the command line overrides the scene camera
+    let mut camera = scene.camera;
+    if options.width.is_some() || options.height.is_some() {
+        let width = options.width.unwrap_or(camera.hsize());
+        let height = options.height.unwrap_or(camera.vsize());
+        camera = camera.with_size(width, height);
+    }
+
+    if let Some(samples) = options.samples {
+        let sampling = camera.sampling();
+        camera.set_sampling(sampling, samples);
+    }
+
+    // switching integrator drops the scene's depth, --max-depth applies to whichever is used
+    let mut integrator = match (options.integrator.as_deref(), camera.integrator()) {
+        (Some("whitted"), Integrator::PathTraced { .. }) => Integrator::Whitted { max_depth: DEFAULT_WHITTED_DEPTH },
+        (Some("path"), Integrator::Whitted { .. }) => Integrator::PathTraced { max_depth: DEFAULT_PATH_DEPTH },
+        (_, scene_integrator) => scene_integrator,
+    };
+    if let Some(max_depth) = options.max_depth {
+        integrator = integrator.with_max_depth(max_depth);
+    }
+    camera.set_integrator(integrator);
+
+    let mut canvas = render_parallel(camera, scene.world, options.threads, 16);
+    canvas.exposure = options.exposure;
+    canvas.tone_map = options.tone_map;
+    let saved = match options.format {
+        Some(format) => canvas.save_as(&options.output, format),
+        None => canvas.save(&options.output),
+    };
+    if let Err(e) = saved {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    println!("wrote {}", options.output);
 }
@@ -1,8 +1,54 @@
-use crate::tuple::{Tuple, TupleProperties, Point, Vector, VectorProperties};
-use crate::light::{PointLight, PointLightProperties};
-use crate::pattern::{black_pattern, Pattern};
+use crate::tuple::{Tuple, TupleProperties, Point, PointProperties, Vector, VectorProperties};
+use crate::light::{Light, LightSample};
+use crate::pattern::{black_pattern, Pattern, Texture, UvMapping};
 use crate::shape_props::ShapeProperties;
-use std::rc::Rc;
+use crate::intersection::Intersection;
+use crate::noise::perturb_normal;
+use std::f64::consts::PI;
+
+// distance between the samples of a bump map's finite differences, in world units
+const BUMP_DELTA: f64 = 0.0001;
+
+// how direct light is turned into colour
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Shading {
+    // ambient, diffuse, specular and shininess
+    Phong,
+    // cook-torrance with a ggx distribution from color, metallic, roughness and
+    // refractive_index, the way metallic/roughness assets are authored
+    Pbr,
+}
+
+// refractive index as a function of wavelength, both formulas take it in micrometres
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Dispersion {
+    // n = a + b / l^2
+    Cauchy(f64, f64),
+    // n^2 = 1 + sum of b_i l^2 / (l^2 - c_i), the form glass catalogues list
+    Sellmeier([f64; 3], [f64; 3]),
+}
+
+impl Dispersion {
+    pub fn refractive_index(&self, nanometres: f64) -> f64 {
+        let l2 = (nanometres / 1000.0) * (nanometres / 1000.0);
+        match self {
+            Dispersion::Cauchy(a, b) => a + b / l2,
+            Dispersion::Sellmeier(b, c) => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                f64::sqrt(n2)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+pub enum NormalMap {
+    // heights from the brightness of a pattern, the normal leans down their gradient;
+    // the factor scales the height
+    Bump(Pattern, f64),
+    // tangent space normals stored as colours: red along u, green along v, blue out of the surface
+    Image(Texture, UvMapping),
+}
 
 #[derive(Clone)]
 pub struct Material {
@@ -14,6 +60,26 @@ pub struct Material {
     pub reflective: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    // replaces refractive_index for rays that carry a wavelength
+    pub dispersion: Option<Dispersion>,
+    // the colour light takes on inside the material, each unit of distance
+    // multiplies it by this colour absorption_density times (beer-lambert);
+    // a density of 0 keeps the material clear
+    pub absorption_color: Tuple,
+    pub absorption_density: f64,
+    // strength of the noise tilting the normal, 0 for a smooth surface
+    pub bump: f64,
+    // size of the bumps in object space
+    pub bump_scale: f64,
+    pub normal_map: Option<NormalMap>,
+    pub shading: Shading,
+    // pbr only: 0 is a dielectric, 1 a metal tinting its reflections with the colour
+    pub metallic: f64,
+    // 0 is polished, 1 fully rough: blurs reflection and refraction, and for pbr
+    // also widens the highlight
+    pub roughness: f64,
+    // rays averaged per blurred reflection or refraction
+    pub gloss_samples: usize,
     has_pattern: bool,
     pattern: Pattern
 }
@@ -24,13 +90,24 @@ pub fn float_eq(a: f64, b: f64) -> bool {
 
 impl PartialEq for Material {
     fn eq(&self, other: &Self) -> bool {
-        return self.color == other.color && f64::abs(self.ambient - other.ambient) < 0.00001 && float_eq(self.diffuse, other.specular)
+        self.color == other.color && f64::abs(self.ambient - other.ambient) < 0.00001 && float_eq(self.diffuse, other.diffuse)
+        && float_eq(self.specular, other.specular)
         && float_eq(self.shininess, other.shininess)
         && float_eq(self.reflective, other.reflective)
         && float_eq(self.transparency, other.transparency)
         && float_eq(self.refractive_index, other.refractive_index)
+        && self.dispersion == other.dispersion
+        && self.absorption_color == other.absorption_color
+        && float_eq(self.absorption_density, other.absorption_density)
+        && float_eq(self.bump, other.bump)
+        && float_eq(self.bump_scale, other.bump_scale)
+        && self.normal_map == other.normal_map
+        && self.shading == other.shading
+        && float_eq(self.metallic, other.metallic)
+        && float_eq(self.roughness, other.roughness)
+        && self.gloss_samples == other.gloss_samples
         && self.has_pattern == other.has_pattern
-
+        && (!self.has_pattern || self.pattern == other.pattern)
     }
 }
 pub trait MaterialProperties {
@@ -45,6 +122,16 @@ pub trait MaterialProperties {
     fn set_pattern(&mut self, pattern: &Pattern);
 
     fn color_at_object(&self, shape: &dyn ShapeProperties, point: Point) -> Tuple;
+
+    // the index seen by a ray of the wavelength, refractive_index without one or
+    // without dispersion
+    fn refractive_index_at(&self, wavelength: Option<f64>) -> f64;
+
+    // fraction of each colour channel left after travelling distance inside the material
+    fn transmittance(&self, distance: f64) -> Tuple;
+
+    // the normal used for shading once bump noise and the normal map have tilted the geometric one
+    fn shading_normal(&self, hit: &Intersection, point: Point, normal: Vector) -> Vector;
 }
 
 impl MaterialProperties for Material {
@@ -57,7 +144,17 @@ impl MaterialProperties for Material {
             shininess,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            absorption_color: Tuple::new(1.0, 1.0, 1.0, 0.0),
+            absorption_density: 0.0,
             reflective: 0.0,
+            bump: 0.0,
+            bump_scale: 1.0,
+            normal_map: None,
+            shading: Shading::Phong,
+            metallic: 0.0,
+            roughness: 0.0,
+            gloss_samples: 8,
             has_pattern: false,
             pattern: black_pattern()
         }
@@ -79,6 +176,16 @@ impl MaterialProperties for Material {
             reflective: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            absorption_color: Tuple::new(1.0, 1.0, 1.0, 0.0),
+            absorption_density: 0.0,
+            bump: 0.0,
+            bump_scale: 1.0,
+            normal_map: None,
+            shading: Shading::Phong,
+            metallic: 0.0,
+            roughness: 0.0,
+            gloss_samples: 8,
             has_pattern: false,
             pattern: black_pattern()
         }
@@ -95,41 +202,182 @@ impl MaterialProperties for Material {
             false => self.color.clone()
         }
     }
+
+    fn refractive_index_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(nm)) => dispersion.refractive_index(nm),
+            _ => self.refractive_index
+        }
+    }
+
+    fn transmittance(&self, distance: f64) -> Tuple {
+        let white = Tuple::new(1.0, 1.0, 1.0, 0.0);
+        if self.absorption_density <= 0.0 || distance <= 0.0 {
+            return white;
+        }
+        // exp(density * distance * ln(color)), a channel of 0 absorbs everything
+        let channel = |c: f64| f64::exp(self.absorption_density * distance * f64::ln(f64::max(c, 1e-9)));
+        let c = self.absorption_color;
+        Tuple::new(channel(c.x), channel(c.y), channel(c.z), 0.0)
+    }
+
+    fn shading_normal(&self, hit: &Intersection, point: Point, normal: Vector) -> Vector {
+        let shape = hit.object;
+        let mut normalv = normal;
+        if self.bump > 0.0 {
+            let p = shape.world_to_object(point);
+            let s = self.bump_scale;
+            normalv = perturb_normal(normalv, Point::new(p.x() / s, p.y() / s, p.z() / s), self.bump);
+        }
+
+        let normal_map = match &self.normal_map {
+            Some(normal_map) => normal_map,
+            None => return normalv,
+        };
+        let (tangent, bitangent) = tangent_frame(hit, point, normalv);
+        match normal_map {
+            NormalMap::Bump(heights, scale) => {
+                let height = |q: Point| {
+                    let c = heights.color_at_object(shape, q);
+                    (c.x + c.y + c.z) / 3.0 * scale
+                };
+                let h = height(point);
+                let dh_du = (height(point + tangent * BUMP_DELTA) - h) / BUMP_DELTA;
+                let dh_dv = (height(point + bitangent * BUMP_DELTA) - h) / BUMP_DELTA;
+                (normalv - tangent * dh_du - bitangent * dh_dv).normalize()
+            }
+            NormalMap::Image(texture, mapping) => {
+                let (u, v) = mapping.map(shape.world_to_object(point));
+                let c = texture.color_at(u, v);
+                let (x, y, z) = (2.0 * c.x - 1.0, 2.0 * c.y - 1.0, 2.0 * c.z - 1.0);
+                (tangent * x + bitangent * y + normalv * z).normalize()
+            }
+        }
+    }
+}
+
+// the shape's tangents made orthonormal to the normal, keeping the side the v tangent points to
+fn tangent_frame(hit: &Intersection, point: Point, normal: Vector) -> (Vector, Vector) {
+    let (u, v) = hit.object.tangents_at_hit(point, hit);
+    let tangent = (u - normal * normal.dot(u)).normalize();
+    let bitangent = normal.cross(tangent);
+    match bitangent.dot(v) < 0.0 {
+        true => (tangent, -1.0 * bitangent),
+        false => (tangent, bitangent)
+    }
+}
+
+// phong or pbr, as the material asks
+pub fn lighting(m: &Material, light: &dyn Light, intersect_point: Point, eyev: Vector, normalv: Vector, samples: &[LightSample], shape: &dyn ShapeProperties) -> Tuple {
+    match m.shading {
+        Shading::Phong => phong_lighting(m, light, intersect_point, eyev, normalv, samples, shape),
+        Shading::Pbr => pbr_lighting(m, light, intersect_point, eyev, normalv, samples, shape),
+    }
 }
 
 // intersect_point: world coord
-pub fn phong_lighting(m: &Material, light: PointLight, intersect_point: Point, eyev: Vector, normalv: Vector, in_shadow: bool, shape: &dyn ShapeProperties) -> Tuple {
+// samples: the light's samples for the point as World::light_samples returns them,
+// only the visible ones add diffuse and specular
+pub fn phong_lighting(m: &Material, light: &dyn Light, intersect_point: Point, eyev: Vector, normalv: Vector, samples: &[LightSample], shape: &dyn ShapeProperties) -> Tuple {
     let black = Tuple::new(0.0, 0.0, 0.0 ,0.0);
-    let effective_color = m.color_at_object(shape, intersect_point).hadamard_product(light.intensity());
+    let effective_color = m.color_at_object(shape, intersect_point).hadamard_product(light.light_intensity());
     // A = L_a * M_a;
     let ambient = effective_color * m.ambient;
-    if in_shadow {
+    let attenuation = light.attenuation(intersect_point);
+    if attenuation <= 0.0 || !samples.iter().any(|sample| sample.visible) {
        return ambient;
     }
 
-    // D = L_d * M_d * (L_dir.dot(normal))
-    let light_direction = (light.position() - intersect_point).normalize();
-    let light_dot_normal = light_direction.dot(normalv);
-    let diffuse: Tuple;
-    let specular: Tuple;
-    if light_dot_normal < 0.0 {
-        // light is on the other side of the surface
-        diffuse = black;
-        specular = black;
-    } else {
-        diffuse = effective_color * m.diffuse * light_dot_normal;
+    // diffuse and specular are averaged over every sample on the light
+    let mut sum = black;
+    for sample in samples.iter().filter(|sample| sample.visible) {
+        // D = L_d * M_d * (L_dir.dot(normal))
+        let light_direction = sample.direction;
+        let light_dot_normal = light_direction.dot(normalv);
+        if light_dot_normal < 0.0 {
+            // light is on the other side of the surface
+            continue;
+        }
+
+        sum = sum + effective_color * m.diffuse * light_dot_normal;
         let reflectv = (-1.0 * light_direction).reflect(normalv);
         let reflectv_dot_eye = reflectv.dot(eyev);
-        if reflectv_dot_eye <= 0.0 {
-            specular = black;
-        } else {
+        if reflectv_dot_eye > 0.0 {
             let factor = reflectv_dot_eye.powf(m.shininess);
-            specular = light.intensity() * m.specular * factor;
+            sum = sum + light.light_intensity() * m.specular * factor;
         }
     }
 
-    ambient + diffuse + specular
+    ambient + sum / samples.len() as f64 * attenuation
+}
+
+// below this a point light's highlight becomes too small to ever be sampled
+const MIN_ROUGHNESS: f64 = 0.05;
+
+// reflectance at normal incidence: from the refractive index for a dielectric,
+// the base colour for a metal
+pub fn base_reflectance(m: &Material, base_color: Tuple) -> Tuple {
+    let r = (m.refractive_index - 1.0) / (m.refractive_index + 1.0);
+    let dielectric = Tuple::new(r * r, r * r, r * r, 0.0);
+    dielectric * (1.0 - m.metallic) + base_color * m.metallic
+}
+
+// schlick's approximation, per colour channel
+pub fn fresnel_schlick(f0: Tuple, cos_theta: f64) -> Tuple {
+    let factor = f64::powi(1.0 - f64::max(cos_theta, 0.0), 5);
+    let white = Tuple::new(1.0, 1.0, 1.0, 0.0);
+    f0 + (white - f0) * factor
+}
+
+// ggx / trowbridge-reitz: how many microfacets face halfway between light and eye
+fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// smith masking-shadowing with the schlick-ggx approximation for one direction
+fn smith_g1(n_dot_x: f64, k: f64) -> f64 {
+    n_dot_x / (n_dot_x * (1.0 - k) + k)
+}
+
+// cook-torrance: a lambert lobe for what the fresnel term lets through, scaled
+// down by metallic, plus the ggx specular lobe; light intensity is taken as the
+// irradiance facing the light, so a white rough dielectric comes out about as
+// bright as the phong diffuse of 1
+pub fn pbr_lighting(m: &Material, light: &dyn Light, intersect_point: Point, eyev: Vector, normalv: Vector, samples: &[LightSample], shape: &dyn ShapeProperties) -> Tuple {
+    let black = Tuple::new(0.0, 0.0, 0.0, 0.0);
+    let base_color = m.color_at_object(shape, intersect_point);
+    let ambient = base_color.hadamard_product(light.light_intensity()) * m.ambient;
+    let attenuation = light.attenuation(intersect_point);
+    let n_dot_v = eyev.dot(normalv);
+    if attenuation <= 0.0 || n_dot_v <= 0.0 || !samples.iter().any(|sample| sample.visible) {
+        return ambient;
+    }
+
+    let f0 = base_reflectance(m, base_color);
+    let roughness = f64::max(m.roughness, MIN_ROUGHNESS);
+    let alpha = roughness * roughness;
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let white = Tuple::new(1.0, 1.0, 1.0, 0.0);
+
+    let mut sum = black;
+    for sample in samples.iter().filter(|sample| sample.visible) {
+        let lightv = sample.direction;
+        let n_dot_l = lightv.dot(normalv);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let halfway = (lightv + eyev).normalize();
+        let fresnel = fresnel_schlick(f0, halfway.dot(eyev));
+        let d = ggx_distribution(f64::max(normalv.dot(halfway), 0.0), alpha);
+        let g = smith_g1(n_dot_l, k) * smith_g1(n_dot_v, k);
+        let specular = fresnel * (PI * d * g / (4.0 * n_dot_l * n_dot_v));
+        let diffuse = (white - fresnel).hadamard_product(base_color) * (1.0 - m.metallic);
+        sum = sum + (diffuse + specular).hadamard_product(light.light_intensity()) * n_dot_l;
+    }
 
+    ambient + sum / samples.len() as f64 * attenuation
 }
 //
 // mod tests {
@@ -0,0 +1,270 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::intersection::{Intersection, EPSILON};
+use crate::bounds::BoundingBox;
+use crate::bvh::Bvh;
+use crate::triangle::triangle_intersection;
+
+// one triangle as indices into the mesh's shared buffers; 32 bits keep the
+// index buffer small and are plenty for any mesh that fits in memory
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Face {
+    pub vertices: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+}
+
+// triangles sharing their vertex, normal and uv buffers, with one material and
+// a bvh over the triangles; every hit carries the triangle index and its
+// barycentric u/v, which is all it takes to shade it
+pub struct Mesh {
+    pub material: Material,
+    pub transform: Transform,
+    pub parent_transform: Transform,
+    pub vertices: Vec<Point>,
+    pub normals: Vec<Vector>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+    // built on demand by build_bvh, without it every triangle is tested
+    bvh: Option<Bvh>,
+}
+
+impl ShapeProperties for Mesh {
+    fn transform(&self) -> Transform {
+        self.transform.clone()
+    }
+
+    fn set_transform(&mut self, t: Transform) {
+        self.transform = t;
+    }
+
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        panic!("a mesh needs the hit to know which triangle, use normal_at_hit");
+    }
+
+    // interpolated when the face has vertex normals, flat otherwise
+    fn normal_at_hit(&self, _p: Point, hit: &Intersection) -> Vector {
+        let face = &self.faces[hit.index];
+        let normal = match face.normals {
+            Some([n1, n2, n3]) => {
+                self.normals[n2 as usize] * hit.u
+                    + self.normals[n3 as usize] * hit.v
+                    + self.normals[n1 as usize] * (1.0 - hit.u - hit.v)
+            }
+            None => {
+                let (_, e1, e2) = self.edges(face);
+                e2.cross(e1)
+            }
+        };
+        self.normal_to_world(normal)
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        panic!("a mesh needs the hit to know which triangle, use tangents_at_hit");
+    }
+
+    // the directions in which the face's texture coordinates grow, or its edges
+    // when it has none
+    fn tangents_at_hit(&self, _p: Point, hit: &Intersection) -> (Vector, Vector) {
+        let face = &self.faces[hit.index];
+        let (_, e1, e2) = self.edges(face);
+        if let Some([t1, t2, t3]) = face.uvs {
+            let (u1, v1) = self.uvs[t1 as usize];
+            let (u2, v2) = self.uvs[t2 as usize];
+            let (u3, v3) = self.uvs[t3 as usize];
+            let (du1, dv1) = (u2 - u1, v2 - v1);
+            let (du2, dv2) = (u3 - u1, v3 - v1);
+            let det = du1 * dv2 - du2 * dv1;
+            if f64::abs(det) > EPSILON {
+                let tangent = (e1 * dv2 - e2 * dv1) * (1.0 / det);
+                let bitangent = (e2 * du1 - e1 * du2) * (1.0 / det);
+                return (self.vector_to_world(tangent), self.vector_to_world(bitangent));
+            }
+        }
+        (self.vector_to_world(e1), self.vector_to_world(e2))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let local_ray = self.transform.inverse() * ray;
+        let mut result: Vec<Intersection> = vec![];
+        let test = |index: usize| {
+            let (p1, e1, e2) = self.edges(&self.faces[index]);
+            if let Some((t, u, v)) = triangle_intersection(&local_ray, p1, e1, e2) {
+                result.push(Intersection::new_with_index(t, self.as_trait(), index, u, v));
+            }
+        };
+        match &self.bvh {
+            Some(bvh) => bvh.traverse(&local_ray, test),
+            None => (0..self.faces.len()).for_each(test),
+        }
+        result
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let mut b = BoundingBox::empty();
+        for v in &self.vertices {
+            b.add_point(*v);
+        }
+        b
+    }
+
+    fn build_bvh(&mut self) {
+        let boxes: Vec<BoundingBox> = self.faces.iter().map(|face| {
+            let mut b = BoundingBox::empty();
+            for v in face.vertices.iter() {
+                b.add_point(self.vertices[*v as usize]);
+            }
+            b
+        }).collect();
+        self.bvh = Some(Bvh::build_from_bounds(&boxes));
+    }
+
+    fn as_trait(&self) -> &dyn ShapeProperties {
+        self
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+
+    fn set_material(&mut self, m: Material) {
+        self.material = m;
+    }
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Point>, normals: Vec<Vector>, uvs: Vec<(f64, f64)>, faces: Vec<Face>) -> Self {
+        Mesh {
+            material: Material::default(),
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            vertices,
+            normals,
+            uvs,
+            faces,
+            bvh: None
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    // texture coordinates at a hit, interpolated like the normals
+    #[cfg(test)]
+    pub fn uv_at_hit(&self, hit: &Intersection) -> Option<(f64, f64)> {
+        let [t1, t2, t3] = self.faces[hit.index].uvs?;
+        let w = 1.0 - hit.u - hit.v;
+        let (u1, v1) = self.uvs[t1 as usize];
+        let (u2, v2) = self.uvs[t2 as usize];
+        let (u3, v3) = self.uvs[t3 as usize];
+        Some((u1 * w + u2 * hit.u + u3 * hit.v, v1 * w + v2 * hit.u + v3 * hit.v))
+    }
+
+    // first corner and the two edges leaving it, as the triangle intersection expects
+    fn edges(&self, face: &Face) -> (Point, Vector, Vector) {
+        let [a, b, c] = face.vertices;
+        let p1 = self.vertices[a as usize];
+        (p1, self.vertices[b as usize] - p1, self.vertices[c as usize] - p1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::float_eq;
+
+    // a unit square in the xy plane split along its diagonal
+    fn square(normals: bool) -> Mesh {
+        let vertices = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let (normal_buffer, face_normals) = match normals {
+            true => (vec![Vector::new(0.0, 0.0, -1.0), Vector::new(0.0, 0.6, -0.8)], [Some([0, 0, 1]), Some([0, 1, 1])]),
+            false => (vec![], [None, None]),
+        };
+        let faces = vec![
+            Face { vertices: [0, 1, 2], normals: face_normals[0], uvs: Some([0, 1, 2]) },
+            Face { vertices: [0, 2, 3], normals: face_normals[1], uvs: Some([0, 2, 3]) },
+        ];
+        Mesh::new(vertices, normal_buffer, uvs, faces)
+    }
+
+    #[test]
+    fn hits_report_the_triangle_and_its_barycentrics() {
+        let m = square(false);
+        let r = Ray::new(Point::new(0.25, 0.75, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = m.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].index, 1);
+        assert!(float_eq(xs[0].t, 2.0));
+        assert!(float_eq(xs[0].u, 0.25));
+        assert!(float_eq(xs[0].v, 0.5));
+        assert_eq!(m.uv_at_hit(&xs[0]), Some((0.25, 0.75)));
+
+        let r = Ray::new(Point::new(0.75, 0.25, -2.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(m.intersect(&r)[0].index, 0);
+        let r = Ray::new(Point::new(1.5, 0.25, -2.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(m.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn the_bvh_finds_the_same_hits() {
+        let mut vertices = vec![];
+        let mut faces = vec![];
+        for i in 0..100 {
+            let x = i as f64 * 2.0;
+            vertices.push(Point::new(x, 0.0, 0.0));
+            vertices.push(Point::new(x + 1.0, 0.0, 0.0));
+            vertices.push(Point::new(x, 1.0, 0.0));
+            let base = 3 * i as u32;
+            faces.push(Face { vertices: [base, base + 1, base + 2], normals: None, uvs: None });
+        }
+        let mut m = Mesh::new(vertices, vec![], vec![], faces);
+        let r = Ray::new(Point::new(120.25, 0.25, -1.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(m.intersect(&r).len(), 1);
+        m.build_bvh();
+        let with_bvh = m.intersect(&r);
+        assert_eq!(with_bvh.len(), 1);
+        assert_eq!(with_bvh[0].index, 60);
+        assert!(m.bounds().max == Point::new(199.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn normals_are_flat_or_interpolated_per_face() {
+        let flat = square(false);
+        let r = Ray::new(Point::new(0.25, 0.75, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = flat.intersect(&r);
+        assert!(flat.normal_at_hit(Point::new(0.25, 0.75, 0.0), &xs[0]) == Vector::new(0.0, 0.0, -1.0));
+
+        let smooth = square(true);
+        let xs = smooth.intersect(&r);
+        // u = 0.25 and v = 0.5 of the blend go to the tilted normal
+        let n = smooth.normal_at_hit(Point::new(0.25, 0.75, 0.0), &xs[0]);
+        assert!(n == (Vector::new(0.0, 0.0, -0.25) + Vector::new(0.0, 0.45, -0.6)).normalize());
+    }
+
+    #[test]
+    fn tangents_follow_the_texture_coordinates() {
+        let mut m = square(false);
+        m.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
+        let r = Ray::new(Point::new(0.5, 1.5, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = m.intersect(&r);
+        let (u, v) = m.tangents_at_hit(Point::new(0.5, 1.5, 0.0), &xs[0]);
+        assert!(u == Vector::new(2.0, 0.0, 0.0));
+        assert!(v == Vector::new(0.0, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::*;
+
+// Ken Perlin's reference permutation, stored once: perm masks indices with 255 so lookups wrap
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225,
+    140, 36, 103, 30, 69, 142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148,
+    247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219, 203, 117, 35, 11, 32,
+    57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122,
+    60, 211, 133, 230, 220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54,
+    65, 25, 63, 161, 1, 216, 80, 73, 209, 76, 132, 187, 208, 89, 18, 169,
+    200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173, 186, 3, 64,
+    52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212,
+    207, 206, 59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213,
+    119, 248, 152, 2, 44, 154, 163, 70, 221, 153, 101, 155, 167, 43, 172, 9,
+    129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232, 178, 185, 112, 104,
+    218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162, 241,
+    81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157,
+    184, 84, 204, 176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93,
+    222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+fn perm(i: usize) -> usize {
+    PERMUTATION[i & 255] as usize
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// dot product with one of 12 gradient directions picked by the hash
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// improved Perlin gradient noise, roughly in [-1, 1] and 0 at integer lattice points
+pub fn perlin(p: Point) -> f64 {
+    let (x, y, z) = (p.x(), p.y(), p.z());
+    let xi = (x.floor() as i64 & 255) as usize;
+    let yi = (y.floor() as i64 & 255) as usize;
+    let zi = (z.floor() as i64 & 255) as usize;
+    let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    lerp(w,
+        lerp(v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+            lerp(u, grad(perm(ab), x, y - 1.0, z), grad(perm(bb), x - 1.0, y - 1.0, z))),
+        lerp(v,
+            lerp(u, grad(perm(aa + 1), x, y, z - 1.0), grad(perm(ba + 1), x - 1.0, y, z - 1.0)),
+            lerp(u, grad(perm(ab + 1), x, y - 1.0, z - 1.0), grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0))))
+}
+
+// three decorrelated noise values, used to displace a point
+pub fn perlin_vector(p: Point) -> Vector {
+    Vector::new(
+        perlin(p),
+        perlin(p + Vector::new(31.416, 47.853, 12.793)),
+        perlin(p + Vector::new(-19.113, 7.247, 63.561))
+    )
+}
+
+// fractal Brownian motion: octaves of noise, each at twice the frequency and half the amplitude
+// of the previous one, normalized back to roughly [-1, 1]
+pub fn fbm(p: Point, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += amplitude * perlin(Point::new(p.x() * frequency, p.y() * frequency, p.z() * frequency));
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+// like fbm but summing absolute values, which creases the noise where it crosses 0; in [0, 1]
+pub fn turbulence(p: Point, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += amplitude * perlin(Point::new(p.x() * frequency, p.y() * frequency, p.z() * frequency)).abs();
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+// tilt a normal by noise sampled at p, for bumpy looking surfaces without extra geometry
+pub fn perturb_normal(normal: Vector, p: Point, amount: f64) -> Vector {
+    (normal + perlin_vector(p) * amount).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_vanishes_on_the_lattice() {
+        assert_eq!(perlin(Point::new(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(perlin(Point::new(3.0, -7.0, 12.0)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_bounded_continuous_and_repeatable() {
+        let mut previous = perlin(Point::new(0.0, 0.3, 0.7));
+        let mut varies = false;
+        for i in 1..2000 {
+            let p = Point::new(i as f64 * 0.01, 0.3, 0.7);
+            let n = perlin(p);
+            assert!((-1.0..=1.0).contains(&n));
+            assert!(f64::abs(n - previous) < 0.05);
+            assert_eq!(n, perlin(p));
+            varies = varies || f64::abs(n) > 0.1;
+            previous = n;
+        }
+        assert!(varies);
+    }
+
+    #[test]
+    fn fbm_and_turbulence_stay_in_range() {
+        for i in 0..500 {
+            let p = Point::new(i as f64 * 0.113, i as f64 * 0.071, 0.5);
+            let f = fbm(p, 6);
+            let t = turbulence(p, 6);
+            assert!((-1.0..=1.0).contains(&f));
+            assert!((0.0..=1.0).contains(&t));
+        }
+        // a single octave is plain noise
+        let p = Point::new(0.3, 0.6, 0.9);
+        assert_eq!(fbm(p, 1), perlin(p));
+        assert_eq!(turbulence(p, 1), perlin(p).abs());
+    }
+
+    #[test]
+    fn perturbing_a_normal_keeps_it_a_unit_vector() {
+        let n = Vector::new(0.0, 1.0, 0.0);
+        let p = Point::new(0.3, 0.6, 0.9);
+        assert!(perturb_normal(n, p, 0.0) == n);
+        let bumped = perturb_normal(n, p, 0.5);
+        assert!(bumped != n);
+        assert!(f64::abs(bumped.mag() - 1.0) < 0.00001);
+    }
+}
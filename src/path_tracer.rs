@@ -0,0 +1,249 @@
+use crate::*;
+use crate::shape_props;
+use crate::intersection::{prepare_computations, schlick, AugIntersection};
+use crate::material::{lighting, base_reflectance, fresnel_schlick, Shading};
+use crate::world::glossy_direction;
+use rand::Rng;
+
+// bounces that always survive before russian roulette may end a path
+const MIN_BOUNCES: u32 = 3;
+
+// depths used when a scene or the command line picks an integrator without one
+pub const DEFAULT_WHITTED_DEPTH: u32 = 5;
+pub const DEFAULT_PATH_DEPTH: u32 = 8;
+
+// how a camera ray is turned into a colour
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    // phong direct light plus perfect mirror and refraction rays, see World::shade_hit;
+    // max_depth bounds the nested reflection and refraction rays
+    Whitted { max_depth: u32 },
+    // monte carlo global illumination, noisy unless given many samples per pixel
+    PathTraced { max_depth: u32 },
+}
+
+impl Integrator {
+    pub fn with_max_depth(self, max_depth: u32) -> Integrator {
+        match self {
+            Integrator::Whitted { .. } => Integrator::Whitted { max_depth },
+            Integrator::PathTraced { .. } => Integrator::PathTraced { max_depth },
+        }
+    }
+}
+
+// a direction around normal, more likely the closer it is to the normal
+// (pdf cos / pi), from two uniform numbers in [0, 1)
+pub fn cosine_sample_hemisphere(normal: Vector, r1: f64, r2: f64) -> Vector {
+    let helper = match f64::abs(normal.x()) > 0.9 {
+        true => Vector::new(0.0, 1.0, 0.0),
+        false => Vector::new(1.0, 0.0, 0.0),
+    };
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+
+    let phi = 2.0 * PI * r1;
+    let r = r2.sqrt();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - r2).sqrt()
+}
+
+fn refracted_direction(comps: &AugIntersection) -> Option<Vector> {
+    let n_ratio = comps.n1 / comps.n2;
+    let cos_i = comps.eyev.dot(comps.normalv);
+    let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+    Some(comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio)
+}
+
+// the material fields are read as follows:
+// - reflective and transparency are the chances of a mirror or a transmission
+//   bounce, the rest of the time the surface is diffuse (they are scaled down
+//   when they add up to more than 1)
+// - a transmission bounce reflects instead with the schlick reflectance
+// - a diffuse bounce gathers the phong diffuse and specular terms of every light
+//   directly, then continues in a cosine weighted direction tinted by the
+//   surface colour times diffuse
+// - paths through an absorbing material are dimmed by its transmittance
+// - ambient is dropped, the light bounced around the scene takes its place
+// - mirror and transmission bounces are spread around the perfect direction
+//   by roughness
+// - a pbr material mirrors with the fresnel reflectance and bounces diffusely
+//   with its colour unless it is metallic
+pub fn path_trace(world: &World, ray: &Ray, max_depth: u32) -> Tuple {
+    let mut rng = rand::thread_rng();
+    let mut radiance = Tuple::new(0.0, 0.0, 0.0, 0.0);
+    let mut throughput = Tuple::new(1.0, 1.0, 1.0, 0.0);
+    let mut ray = *ray;
+
+    for bounce in 0..max_depth {
+        let xs = world.intersect(&ray);
+        let hit = match shape_props::hit(xs.clone()) {
+            Some(hit) => hit,
+            None => break,
+        };
+        let comps = prepare_computations(&hit, &ray, &xs);
+        if let Some(medium) = comps.medium {
+            throughput = throughput.hadamard_product(medium.material().transmittance(hit.t * ray.direction().mag()));
+        }
+        let mut material = comps.object.material();
+
+        let base_color = material.color_at_object(comps.object, comps.over_point);
+        // a pbr surface mirrors as often as fresnel says, tinting the path with it
+        let (reflective, tint) = match material.shading {
+            Shading::Phong => (f64::max(material.reflective, 0.0), Tuple::new(1.0, 1.0, 1.0, 0.0)),
+            Shading::Pbr => {
+                let fresnel = fresnel_schlick(base_reflectance(&material, base_color), comps.eyev.dot(comps.shading_normalv));
+                let chance = (fresnel.x + fresnel.y + fresnel.z) / 3.0;
+                (chance, fresnel * (1.0 / f64::max(chance, 0.0001)))
+            }
+        };
+        let transparency = f64::max(material.transparency, 0.0);
+        let scale = f64::max(reflective + transparency, 1.0);
+        let choice = rng.gen::<f64>() * scale;
+
+        if choice < transparency {
+            let reflectance = schlick(&comps);
+            ray = match refracted_direction(&comps) {
+                Some(direction) if rng.gen::<f64>() >= reflectance => {
+                    let direction = glossy_direction(direction, -1.0 * comps.normalv, material.roughness, rng.gen(), rng.gen());
+                    Ray::new(comps.under_point, direction)
+                }
+                _ => Ray::new(comps.over_point, glossy_direction(comps.reflectv, comps.normalv, material.roughness, rng.gen(), rng.gen())),
+            }.with_wavelength(comps.wavelength);
+        } else if choice < transparency + reflective {
+            throughput = throughput.hadamard_product(tint);
+            ray = Ray::new(comps.over_point, glossy_direction(comps.reflectv, comps.normalv, material.roughness, rng.gen(), rng.gen()))
+                .with_wavelength(comps.wavelength);
+        } else {
+            material.ambient = 0.0;
+            for light in &world.lights {
+                let direct = lighting(
+                    &material,
+                    light.as_ref(),
+                    comps.over_point,
+                    comps.eyev,
+                    comps.shading_normalv,
+                    &world.light_samples(comps.over_point, light.as_ref()),
+                    comps.object
+                );
+                radiance = radiance + throughput.hadamard_product(direct);
+            }
+
+            let albedo = match material.shading {
+                Shading::Phong => base_color * material.diffuse,
+                Shading::Pbr => base_color * (1.0 - material.metallic),
+            };
+            throughput = throughput.hadamard_product(albedo);
+            let direction = cosine_sample_hemisphere(comps.normalv, rng.gen(), rng.gen());
+            ray = Ray::new(comps.over_point, direction).with_wavelength(comps.wavelength);
+        }
+
+        // russian roulette: end dim paths early and boost the survivors to stay unbiased
+        if bounce + 1 >= MIN_BOUNCES {
+            let survival = f64::min(f64::max(throughput.x, f64::max(throughput.y, throughput.z)), 0.95);
+            if survival <= 0.0 || rng.gen::<f64>() >= survival {
+                break;
+            }
+            throughput = throughput * (1.0 / survival);
+        }
+    }
+    radiance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plane::plane;
+    use crate::sphere::sphere;
+    use crate::light::PointLight;
+    use crate::material::float_eq;
+    use crate::shape_props::ShapeProperties;
+
+    #[test]
+    fn cosine_samples_stay_in_the_hemisphere() {
+        let n = Vector::new(0.0, 0.0, -1.0);
+        let mut rng = rand::thread_rng();
+        let mut mean_cos = 0.0;
+        for _ in 0..2000 {
+            let d = cosine_sample_hemisphere(n, rng.gen(), rng.gen());
+            assert!(float_eq(d.mag(), 1.0));
+            assert!(d.dot(n) >= 0.0);
+            mean_cos += d.dot(n) / 2000.0;
+        }
+        // the mean cosine of a cosine weighted hemisphere is 2/3
+        assert!(f64::abs(mean_cos - 2.0 / 3.0) < 0.03);
+        assert!(cosine_sample_hemisphere(n, 0.3, 0.0) == n);
+    }
+
+    // a white floor lit from above, with a red wall standing on it
+    fn room(wall_color: Tuple) -> World {
+        let mut w = World::new();
+        w.objects_mut().clear();
+        w.lights = vec![Box::new(PointLight::new(Point::new(0.0, 5.0, -2.0), Tuple::new(1.0, 1.0, 1.0, 0.0)))];
+        let mut floor = plane();
+        floor.material.specular = 0.0;
+        w.add_object(Box::new(floor));
+        let mut wall = plane();
+        wall.set_transform(Transform::new().rotate_x(PI / 2.0).translate(0.0, 0.0, 1.0));
+        wall.material = Material::new(wall_color, 0.1, 0.9, 0.0, 200.0);
+        w.add_object(Box::new(wall));
+        w
+    }
+
+    fn average(world: &World, ray: &Ray, max_depth: u32, samples: usize) -> Tuple {
+        let mut sum = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            sum = sum + path_trace(world, ray, max_depth);
+        }
+        sum * (1.0 / samples as f64)
+    }
+
+    #[test]
+    fn a_single_bounce_is_direct_light_without_ambient() {
+        let w = room(Tuple::new(1.0, 0.0, 0.0, 0.0));
+        let r = Ray::new(Point::new(0.0, 1.0, -3.0), Vector::new(0.0, -1.0, 0.0));
+        let c = path_trace(&w, &r, 1);
+        // lambert term of the floor at (0, 0, -3), no ambient
+        let to_light = Vector::new(0.0, 5.0, 1.0).normalize();
+        let expected = 0.9 * to_light.dot(Vector::new(0.0, 1.0, 0.0));
+        assert!(f64::abs(c.x - expected) < 0.0001);
+        assert!(f64::abs(c.y - expected) < 0.0001);
+    }
+
+    #[test]
+    fn diffuse_interreflection_bleeds_colour() {
+        let r = Ray::new(Point::new(0.0, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0));
+        let red_wall = average(&room(Tuple::new(1.0, 0.0, 0.0, 0.0)), &r, 4, 400);
+        // the floor next to the red wall picks up red but no extra green
+        assert!(red_wall.x > red_wall.y + 0.02);
+        assert!(float_eq(red_wall.y, red_wall.z));
+
+    }
+
+    #[test]
+    fn mirrors_and_glass_pass_the_path_on() {
+        let mut w = room(Tuple::new(1.0, 1.0, 1.0, 0.0));
+        let mut mirror = sphere();
+        mirror.material.reflective = 1.0;
+        mirror.set_transform(Transform::new().translate(0.0, 1.0, -3.0));
+        w.add_object(Box::new(mirror));
+
+        // straight down onto the mirror ball: the reflection goes back up and is lost
+        let r = Ray::new(Point::new(0.0, 5.0, -3.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(path_trace(&w, &r, 5) == Tuple::new(0.0, 0.0, 0.0, 0.0));
+
+        // the same ball as clear glass lets the floor below show through, lit
+        // from the side so the ball casts no shadow there
+        w.objects_mut().pop();
+        w.lights = vec![Box::new(PointLight::new(Point::new(5.0, 1.0, -3.0), Tuple::new(1.0, 1.0, 1.0, 0.0)))];
+        let mut glass = sphere();
+        glass.material.transparency = 1.0;
+        glass.material.refractive_index = 1.0;
+        glass.set_transform(Transform::new().translate(0.0, 2.5, -3.0));
+        w.add_object(Box::new(glass));
+        let c = average(&w, &r, 5, 20);
+        assert!(c.x > 0.15);
+    }
+}
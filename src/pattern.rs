@@ -2,53 +2,206 @@
 use crate::*;
 use self::Kind::*;
 use crate::shape_props::ShapeProperties;
-use std::rc::Rc;
+use crate::noise::{perlin_vector, fbm, turbulence};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// how a 3D point on a shape is flattened into (u, v) in [0, 1)
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UvMapping {
+    Spherical,
+    Planar,
+    Cylindrical,
+}
+
+impl UvMapping {
+    pub fn map(&self, p: Point) -> (f64, f64) {
+        match self {
+            UvMapping::Spherical => spherical_map(p),
+            UvMapping::Planar => planar_map(p),
+            UvMapping::Cylindrical => cylindrical_map(p),
+        }
+    }
+}
+
+// an image sampled with bilinear filtering, shared between clones of a pattern
+#[derive(Clone)]
+pub struct Texture {
+    image: Arc<Canvas>,
+}
+
+impl PartialEq for Texture {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.image, &other.image)
+    }
+}
+
+impl Texture {
+    pub fn new(image: Canvas) -> Self {
+        Texture { image: Arc::new(image) }
+    }
+
+    // v runs bottom to top, image rows top to bottom
+    pub fn color_at(&self, u: f64, v: f64) -> Tuple {
+        let width = self.image.width();
+        let height = self.image.height();
+        let x = u.clamp(0.0, 1.0) * (width - 1) as f64;
+        let y = (1.0 - v.clamp(0.0, 1.0)) * (height - 1) as f64;
+
+        let x0 = x.floor() as u32;
+        let y0 = y.floor() as u32;
+        let x1 = u32::min(x0 + 1, width - 1);
+        let y1 = u32::min(y0 + 1, height - 1);
+        let fx = x - x0 as f64;
+        let fy = y - y0 as f64;
+
+        let top = self.image.pixel_at(x0, y0) * (1.0 - fx) + self.image.pixel_at(x1, y0) * fx;
+        let bottom = self.image.pixel_at(x0, y1) * (1.0 - fx) + self.image.pixel_at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
 
 #[derive(PartialEq, Clone)]
 pub enum Kind {
     Solid(Tuple),
-    Stripe(Tuple, Tuple),
-    Gradient(Tuple, Tuple),
-    Checkers(Tuple, Tuple),
-    Ring(Tuple, Tuple),
+    // the two-way patterns alternate between sub patterns, each with its own transform
+    Stripe(Box<Pattern>, Box<Pattern>),
+    Gradient(Box<Pattern>, Box<Pattern>),
+    Checkers(Box<Pattern>, Box<Pattern>),
+    Ring(Box<Pattern>, Box<Pattern>),
+    // weighted average, the weight going to the second pattern
+    Blend(Box<Pattern>, Box<Pattern>, f64),
+    // displace the point with noise scaled by the factor before sampling
+    Perturbed(Box<Pattern>, f64),
+    // noise driven blends between two sub patterns; the factor is the strength of the noise
+    Marble(Box<Pattern>, Box<Pattern>, f64),
+    Wood(Box<Pattern>, Box<Pattern>, f64),
+    Clouds(Box<Pattern>, Box<Pattern>),
+    TextureMap(Texture, UvMapping),
+    // faces in the order left, front, right, back, up, down
+    CubeMap(Box<[Texture; 6]>),
+}
+
+pub fn spherical_map(p: Point) -> (f64, f64) {
+    let theta = p.x().atan2(p.z());
+    let radius = (p.x() * p.x() + p.y() * p.y() + p.z() * p.z()).sqrt();
+    let phi = (p.y() / radius).acos();
+    let raw_u = theta / (2.0 * PI);
+    // flip u so it grows counter clockwise seen from above
+    let u = 1.0 - (raw_u + 0.5);
+    let v = 1.0 - phi / PI;
+    (u, v)
 }
 
-fn alternate(t: i64, a: Tuple, b: Tuple) -> Tuple {
+pub fn planar_map(p: Point) -> (f64, f64) {
+    (p.x().rem_euclid(1.0), p.z().rem_euclid(1.0))
+}
+
+pub fn cylindrical_map(p: Point) -> (f64, f64) {
+    let theta = p.x().atan2(p.z());
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    (u, p.y().rem_euclid(1.0))
+}
+
+// index into the CubeMap faces of the face the point lies on
+fn cube_face(p: Point) -> usize {
+    let coord = f64::max(f64::max(p.x().abs(), p.y().abs()), p.z().abs());
+    if coord == p.x() {
+        2
+    } else if coord == -p.x() {
+        0
+    } else if coord == p.y() {
+        4
+    } else if coord == -p.y() {
+        5
+    } else if coord == p.z() {
+        1
+    } else {
+        3
+    }
+}
+
+fn cube_uv(face: usize, p: Point) -> (f64, f64) {
+    let (u, v) = match face {
+        0 => (p.z() + 1.0, p.y() + 1.0),
+        1 => (p.x() + 1.0, p.y() + 1.0),
+        2 => (1.0 - p.z(), p.y() + 1.0),
+        3 => (1.0 - p.x(), p.y() + 1.0),
+        4 => (p.x() + 1.0, 1.0 - p.z()),
+        _ => (p.x() + 1.0, p.z() + 1.0),
+    };
+    ((u % 2.0) / 2.0, (v % 2.0) / 2.0)
+}
+
+fn alternate(t: i64, a: &Pattern, b: &Pattern, point: Point) -> Tuple {
     match t % 2 == 0 {
-        true => a,
-        false => b
+        true => a.color_at_parent(point),
+        false => b.color_at_parent(point)
     }
 }
 
+const NOISE_OCTAVES: u32 = 6;
+
+fn mix(t: f64, a: &Pattern, b: &Pattern, point: Point) -> Tuple {
+    let color_a = a.color_at_parent(point);
+    let color_b = b.color_at_parent(point);
+    color_a + (color_b - color_a) * t
+}
+
 impl Kind {
     fn color_at(&self, point: Point) -> Tuple {
         match self {
             Solid(colour) => colour.clone(),
             Stripe(a, b) => {
                 let t = point.x().floor() as i64;
-                let color_a = a.clone();
-                let color_b = b.clone();
-                alternate(t, color_a, color_b)
+                alternate(t, a, b, point)
             },
             Gradient(a, b) => {
-                let color_a = a.clone();
-                let color_b = b.clone();
+                let color_a = a.color_at_parent(point);
+                let color_b = b.color_at_parent(point);
                 let distance = color_b - color_a;
                 let fraction = point.x() - point.x().floor();
                 let df = distance * fraction;
                 color_a + df
             }
             Ring(a, b) => {
-                let color_a = a.clone();
-                let color_b = b.clone();
                 let t = (point.x() * point.x() + point.z() * point.z()).sqrt().floor() as i64;
-                alternate(t, color_a, color_b)
+                alternate(t, a, b, point)
             }
             Checkers(a, b) => {
-                let color_a = a.clone();
-                let color_b = b.clone();
                 let t = (point.x().floor() + point.y().floor() + point.z().floor()) as i64;
-                alternate(t, color_a, color_b)
+                alternate(t, a, b, point)
+            }
+            Blend(a, b, weight) => {
+                a.color_at_parent(point) * (1.0 - weight) + b.color_at_parent(point) * *weight
+            }
+            Perturbed(inner, scale) => {
+                let jittered = point + perlin_vector(point) * *scale;
+                inner.color_at_parent(jittered)
+            }
+            // veins running along y and z, bent by turbulence
+            Marble(a, b, strength) => {
+                let phase = point.x() * PI + strength * turbulence(point, NOISE_OCTAVES) * PI;
+                mix(0.5 + 0.5 * phase.sin(), a, b, point)
+            }
+            // growth rings around the y axis, warped by low frequency noise
+            Wood(a, b, strength) => {
+                let r = (point.x() * point.x() + point.z() * point.z()).sqrt() + strength * fbm(point, 2);
+                mix(r - r.floor(), a, b, point)
+            }
+            Clouds(a, b) => {
+                let t = (0.5 + 0.5 * fbm(point, NOISE_OCTAVES)).clamp(0.0, 1.0);
+                mix(t, a, b, point)
+            }
+            TextureMap(texture, mapping) => {
+                let (u, v) = mapping.map(point);
+                texture.color_at(u, v)
+            }
+            CubeMap(faces) => {
+                let face = cube_face(point);
+                let (u, v) = cube_uv(face, point);
+                faces[face].color_at(u, v)
             }
         }
     }
@@ -69,8 +222,16 @@ impl Pattern {
         self.kind.color_at(point)
     }
 
+    // colour at a point given in the space of the enclosing pattern
+    fn color_at_parent(&self, point: Point) -> Tuple {
+        match &self.kind {
+            Solid(colour) => *colour,
+            kind => kind.color_at(self.transform.inverse() * point)
+        }
+    }
+
     pub fn color_at_object(&self, shape: &dyn ShapeProperties, point: Point) -> Tuple {
-        let object_point = shape.transform().inverse() * point;
+        let object_point = shape.world_to_object(point);
         let pattern_point = self.transform.inverse() * object_point;
         self.kind.color_at(pattern_point)
     }
@@ -84,30 +245,122 @@ pub fn black_pattern() -> Pattern {
     }
 }
 
+pub fn solid_pattern(color: Tuple) -> Pattern {
+    Pattern {
+        kind: Solid(color),
+        transform: Transform::new()
+    }
+}
+
 pub fn stripe_pattern(color_a: Tuple, color_b: Tuple) -> Pattern {
     Pattern {
-        kind: Stripe(color_a, color_b),
+        kind: Stripe(Box::new(solid_pattern(color_a)), Box::new(solid_pattern(color_b))),
         transform: Transform::new()
     }
 }
 
 pub fn gradient_pattern(a: Tuple, b: Tuple) -> Pattern {
     Pattern {
-        kind: Gradient(a, b),
+        kind: Gradient(Box::new(solid_pattern(a)), Box::new(solid_pattern(b))),
         transform: Transform::new()
     }
 }
 
 pub fn checkers_pattern(a: Tuple, b: Tuple) -> Pattern {
     Pattern {
-        kind: Checkers(a, b),
+        kind: Checkers(Box::new(solid_pattern(a)), Box::new(solid_pattern(b))),
         transform: Transform::new(),
     }
 }
 
 pub fn ring_pattern(a: Tuple, b: Tuple) -> Pattern {
     Pattern {
-        kind: Ring(a, b),
+        kind: Ring(Box::new(solid_pattern(a)), Box::new(solid_pattern(b))),
+        transform: Transform::new()
+    }
+}
+
+pub fn nested_stripe_pattern(a: Pattern, b: Pattern) -> Pattern {
+    Pattern {
+        kind: Stripe(Box::new(a), Box::new(b)),
+        transform: Transform::new()
+    }
+}
+
+pub fn nested_gradient_pattern(a: Pattern, b: Pattern) -> Pattern {
+    Pattern {
+        kind: Gradient(Box::new(a), Box::new(b)),
+        transform: Transform::new()
+    }
+}
+
+pub fn nested_checkers_pattern(a: Pattern, b: Pattern) -> Pattern {
+    Pattern {
+        kind: Checkers(Box::new(a), Box::new(b)),
+        transform: Transform::new()
+    }
+}
+
+pub fn nested_ring_pattern(a: Pattern, b: Pattern) -> Pattern {
+    Pattern {
+        kind: Ring(Box::new(a), Box::new(b)),
+        transform: Transform::new()
+    }
+}
+
+pub fn blend_pattern(a: Pattern, b: Pattern, weight: f64) -> Pattern {
+    Pattern {
+        kind: Blend(Box::new(a), Box::new(b), weight),
+        transform: Transform::new()
+    }
+}
+
+pub fn perturbed_pattern(inner: Pattern, scale: f64) -> Pattern {
+    Pattern {
+        kind: Perturbed(Box::new(inner), scale),
+        transform: Transform::new()
+    }
+}
+
+pub fn marble_pattern(a: Pattern, b: Pattern, strength: f64) -> Pattern {
+    Pattern {
+        kind: Marble(Box::new(a), Box::new(b), strength),
+        transform: Transform::new()
+    }
+}
+
+pub fn wood_pattern(a: Pattern, b: Pattern, strength: f64) -> Pattern {
+    Pattern {
+        kind: Wood(Box::new(a), Box::new(b), strength),
+        transform: Transform::new()
+    }
+}
+
+pub fn clouds_pattern(a: Pattern, b: Pattern) -> Pattern {
+    Pattern {
+        kind: Clouds(Box::new(a), Box::new(b)),
+        transform: Transform::new()
+    }
+}
+
+pub fn texture_map_pattern(image: Canvas, mapping: UvMapping) -> Pattern {
+    Pattern {
+        kind: TextureMap(Texture::new(image), mapping),
+        transform: Transform::new()
+    }
+}
+
+pub fn cube_map_pattern(faces: [Canvas; 6]) -> Pattern {
+    let [left, front, right, back, up, down] = faces;
+    Pattern {
+        kind: CubeMap(Box::new([
+            Texture::new(left),
+            Texture::new(front),
+            Texture::new(right),
+            Texture::new(back),
+            Texture::new(up),
+            Texture::new(down)
+        ])),
         transform: Transform::new()
     }
 }
@@ -115,6 +368,231 @@ pub fn ring_pattern(a: Tuple, b: Tuple) -> Pattern {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::canvas::CanvasProperties;
+    use crate::sphere::sphere;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    fn solid_image(color: Tuple) -> Canvas {
+        let mut image = Canvas::new(2, 2);
+        for y in 0..2 {
+            for x in 0..2 {
+                image.write_pixel(x, y, color);
+            }
+        }
+        image
+    }
+
+    fn uv_eq(actual: (f64, f64), expected: (f64, f64)) -> bool {
+        f64::abs(actual.0 - expected.0) < 0.0001 && f64::abs(actual.1 - expected.1) < 0.0001
+    }
+
+    #[test]
+    fn using_a_spherical_mapping_on_a_3d_point() {
+        assert!(uv_eq(spherical_map(Point::new(0.0, 0.0, -1.0)), (0.0, 0.5)));
+        assert!(uv_eq(spherical_map(Point::new(1.0, 0.0, 0.0)), (0.25, 0.5)));
+        assert!(uv_eq(spherical_map(Point::new(0.0, 0.0, 1.0)), (0.5, 0.5)));
+        assert!(uv_eq(spherical_map(Point::new(-1.0, 0.0, 0.0)), (0.75, 0.5)));
+        assert!(uv_eq(spherical_map(Point::new(0.0, 1.0, 0.0)), (0.5, 1.0)));
+        assert!(uv_eq(spherical_map(Point::new(0.0, -1.0, 0.0)), (0.5, 0.0)));
+        assert!(uv_eq(spherical_map(Point::new(f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0, 0.0)), (0.25, 0.75)));
+    }
+
+    #[test]
+    fn using_a_planar_mapping_on_a_3d_point() {
+        assert!(uv_eq(planar_map(Point::new(0.25, 0.0, 0.5)), (0.25, 0.5)));
+        assert!(uv_eq(planar_map(Point::new(0.25, 0.0, -0.25)), (0.25, 0.75)));
+        assert!(uv_eq(planar_map(Point::new(0.25, 0.5, -0.25)), (0.25, 0.75)));
+        assert!(uv_eq(planar_map(Point::new(1.25, 0.0, 0.5)), (0.25, 0.5)));
+        assert!(uv_eq(planar_map(Point::new(0.25, 0.0, -1.75)), (0.25, 0.25)));
+        assert!(uv_eq(planar_map(Point::new(1.0, 0.0, -1.0)), (0.0, 0.0)));
+    }
+
+    #[test]
+    fn using_a_cylindrical_mapping_on_a_3d_point() {
+        assert!(uv_eq(cylindrical_map(Point::new(0.0, 0.0, -1.0)), (0.0, 0.0)));
+        assert!(uv_eq(cylindrical_map(Point::new(0.0, 0.5, -1.0)), (0.0, 0.5)));
+        assert!(uv_eq(cylindrical_map(Point::new(0.0, 1.0, -1.0)), (0.0, 0.0)));
+        assert!(uv_eq(cylindrical_map(Point::new(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2)), (0.125, 0.5)));
+        assert!(uv_eq(cylindrical_map(Point::new(1.0, 0.5, 0.0)), (0.25, 0.5)));
+        assert!(uv_eq(cylindrical_map(Point::new(-FRAC_1_SQRT_2, 0.75, FRAC_1_SQRT_2)), (0.625, 0.75)));
+    }
+
+    #[test]
+    fn identifying_the_face_of_a_cube_from_a_point() {
+        assert_eq!(cube_face(Point::new(-1.0, 0.5, -0.25)), 0);
+        assert_eq!(cube_face(Point::new(1.1, -0.75, 0.8)), 2);
+        assert_eq!(cube_face(Point::new(0.1, 0.6, 0.9)), 1);
+        assert_eq!(cube_face(Point::new(-0.7, 0.0, -2.0)), 3);
+        assert_eq!(cube_face(Point::new(0.5, 1.0, 0.9)), 4);
+        assert_eq!(cube_face(Point::new(-0.2, -1.3, 1.1)), 5);
+    }
+
+    #[test]
+    fn uv_mapping_the_faces_of_a_cube() {
+        assert!(uv_eq(cube_uv(1, Point::new(-0.5, 0.5, 1.0)), (0.25, 0.75)));
+        assert!(uv_eq(cube_uv(3, Point::new(0.5, -0.5, -1.0)), (0.25, 0.25)));
+        assert!(uv_eq(cube_uv(0, Point::new(-1.0, 0.5, -0.5)), (0.25, 0.75)));
+        assert!(uv_eq(cube_uv(2, Point::new(1.0, -0.5, 0.5)), (0.25, 0.25)));
+        assert!(uv_eq(cube_uv(4, Point::new(-0.5, 1.0, -0.5)), (0.25, 0.75)));
+        assert!(uv_eq(cube_uv(5, Point::new(0.5, -1.0, -0.5)), (0.75, 0.25)));
+    }
+
+    #[test]
+    fn textures_are_sampled_bilinearly() {
+        let mut image = Canvas::new(2, 2);
+        image.write_pixel(0, 0, Tuple::new(1.0, 0.0, 0.0, 0.0));
+        image.write_pixel(1, 0, Tuple::new(0.0, 1.0, 0.0, 0.0));
+        image.write_pixel(0, 1, Tuple::new(0.0, 0.0, 1.0, 0.0));
+        image.write_pixel(1, 1, Tuple::new(1.0, 1.0, 1.0, 0.0));
+        let texture = Texture::new(image);
+
+        // (0, 1) is the top left corner of the image
+        assert!(texture.color_at(0.0, 1.0) == Tuple::new(1.0, 0.0, 0.0, 0.0));
+        assert!(texture.color_at(1.0, 0.0) == Tuple::new(1.0, 1.0, 1.0, 0.0));
+        assert!(texture.color_at(0.5, 1.0) == Tuple::new(0.5, 0.5, 0.0, 0.0));
+        assert!(texture.color_at(0.5, 0.5) == Tuple::new(0.5, 0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn a_texture_map_pattern_uses_its_mapping() {
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Tuple::new(1.0, 0.0, 0.0, 0.0));
+        image.write_pixel(1, 0, Tuple::new(0.0, 0.0, 1.0, 0.0));
+        let pattern = texture_map_pattern(image, UvMapping::Planar);
+        assert!(pattern.color_at(Point::new(0.0, 0.0, 0.0)) == Tuple::new(1.0, 0.0, 0.0, 0.0));
+        assert!(pattern.color_at(Point::new(0.999, 5.0, 0.3)).z > 0.99);
+    }
+
+    #[test]
+    fn a_cube_map_picks_the_image_of_each_face() {
+        let colors = [
+            Tuple::new(1.0, 1.0, 0.0, 0.0),
+            Tuple::new(0.0, 1.0, 1.0, 0.0),
+            Tuple::new(1.0, 0.0, 0.0, 0.0),
+            Tuple::new(0.0, 1.0, 0.0, 0.0),
+            Tuple::new(1.0, 0.5, 0.0, 0.0),
+            Tuple::new(1.0, 1.0, 1.0, 0.0),
+        ];
+        let pattern = cube_map_pattern([
+            solid_image(colors[0]),
+            solid_image(colors[1]),
+            solid_image(colors[2]),
+            solid_image(colors[3]),
+            solid_image(colors[4]),
+            solid_image(colors[5]),
+        ]);
+        assert!(pattern.color_at(Point::new(-1.0, 0.0, 0.0)) == colors[0]);
+        assert!(pattern.color_at(Point::new(0.0, 0.0, 1.0)) == colors[1]);
+        assert!(pattern.color_at(Point::new(1.0, 0.2, 0.0)) == colors[2]);
+        assert!(pattern.color_at(Point::new(0.0, 0.0, -1.0)) == colors[3]);
+        assert!(pattern.color_at(Point::new(0.3, 1.0, 0.0)) == colors[4]);
+        assert!(pattern.color_at(Point::new(0.0, -1.0, 0.5)) == colors[5]);
+    }
+
+    fn white() -> Tuple {
+        Tuple::new(1.0, 1.0, 1.0, 0.0)
+    }
+
+    fn black() -> Tuple {
+        Tuple::new(0.0, 0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn stripes_can_alternate_between_patterns() {
+        let red = Tuple::new(1.0, 0.0, 0.0, 0.0);
+        let mut checkers = checkers_pattern(white(), black());
+        checkers.set_transform(&Transform::new().scaling(0.5, 0.5, 0.5));
+        let pattern = nested_stripe_pattern(checkers, solid_pattern(red));
+
+        // the checkers are half as big as the stripes
+        assert!(pattern.color_at(Point::new(0.25, 0.0, 0.0)) == white());
+        assert!(pattern.color_at(Point::new(0.75, 0.0, 0.0)) == black());
+        assert!(pattern.color_at(Point::new(1.5, 0.0, 0.0)) == red);
+    }
+
+    #[test]
+    fn sub_patterns_compose_with_the_outer_transform() {
+        let mut inner = stripe_pattern(white(), black());
+        inner.set_transform(&Transform::new().translate(0.5, 0.0, 0.0));
+        let mut pattern = nested_ring_pattern(inner, solid_pattern(black()));
+        pattern.set_transform(&Transform::new().scaling(2.0, 2.0, 2.0));
+        let sphere = sphere();
+
+        // object 1.2 -> pattern 0.6 -> inner stripe 0.1
+        assert!(pattern.color_at_object(&sphere, Point::new(1.2, 0.0, 0.0)) == white());
+        // object 0.4 -> pattern 0.2 -> inner stripe -0.3
+        assert!(pattern.color_at_object(&sphere, Point::new(0.4, 0.0, 0.0)) == black());
+    }
+
+    #[test]
+    fn a_blend_averages_two_patterns() {
+        let a = stripe_pattern(white(), black());
+        let mut b = stripe_pattern(white(), black());
+        b.set_transform(&Transform::new().rotate_y(PI / 2.0));
+        let pattern = blend_pattern(a, b, 0.25);
+
+        let c = pattern.color_at(Point::new(0.5, 0.0, -0.5));
+        assert!(c == white());
+        let c = pattern.color_at(Point::new(1.5, 0.0, -0.5));
+        assert!(c == Tuple::new(0.25, 0.25, 0.25, 0.0));
+        let c = pattern.color_at(Point::new(0.5, 0.0, 0.5));
+        assert!(c == Tuple::new(0.75, 0.75, 0.75, 0.0));
+    }
+
+    #[test]
+    fn a_perturbed_pattern_jitters_the_point() {
+        let stripes = stripe_pattern(white(), black());
+        let unchanged = perturbed_pattern(stripes.clone(), 0.0);
+        let perturbed = perturbed_pattern(stripes.clone(), 0.5);
+
+        let mut differs = false;
+        for i in 0..200 {
+            let p = Point::new(i as f64 * 0.037, 0.31, 0.47);
+            assert!(unchanged.color_at(p) == stripes.color_at(p));
+            differs = differs || perturbed.color_at(p) != stripes.color_at(p);
+        }
+        assert!(differs);
+    }
+
+    fn between(c: Tuple, lo: f64, hi: f64) -> bool {
+        c.x >= lo - 0.00001 && c.x <= hi + 0.00001 && c.x == c.y && c.y == c.z
+    }
+
+    #[test]
+    fn marble_without_noise_is_a_sine_wave_along_x() {
+        let pattern = marble_pattern(solid_pattern(black()), solid_pattern(white()), 0.0);
+        assert!(pattern.color_at(Point::new(0.0, 0.3, 0.7)) == Tuple::new(0.5, 0.5, 0.5, 0.0));
+        assert!(pattern.color_at(Point::new(0.5, 0.3, 0.7)) == white());
+        assert!(pattern.color_at(Point::new(1.5, 0.3, 0.7)) == black());
+    }
+
+    #[test]
+    fn wood_without_noise_is_a_gradient_in_rings() {
+        let pattern = wood_pattern(solid_pattern(black()), solid_pattern(white()), 0.0);
+        assert!(pattern.color_at(Point::new(0.25, 0.0, 0.0)) == Tuple::new(0.25, 0.25, 0.25, 0.0));
+        assert!(pattern.color_at(Point::new(0.0, 5.0, 1.75)) == Tuple::new(0.75, 0.75, 0.75, 0.0));
+    }
+
+    #[test]
+    fn noise_patterns_mix_between_their_sub_patterns() {
+        let patterns = [
+            marble_pattern(solid_pattern(black()), solid_pattern(white()), 2.0),
+            wood_pattern(solid_pattern(black()), solid_pattern(white()), 0.5),
+            clouds_pattern(solid_pattern(black()), solid_pattern(white())),
+        ];
+        for pattern in patterns.iter() {
+            let mut distinct = vec![];
+            for i in 0..100 {
+                let c = pattern.color_at(Point::new(i as f64 * 0.137, 0.25, i as f64 * 0.051));
+                assert!(between(c, 0.0, 1.0));
+                if !distinct.contains(&c) {
+                    distinct.push(c);
+                }
+            }
+            // a smooth blend, not a two colour pattern
+            assert!(distinct.len() > 10);
+        }
+    }
 
     #[test]
     fn test_pattern_equal() {
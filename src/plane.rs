@@ -1,12 +1,13 @@
 use crate::*;
 use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
 use crate::intersection::Intersection;
-use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct Plane {
     pub material: Material,
     pub transform: Transform,
+    pub parent_transform: Transform,
 }
 
 impl ShapeProperties for Plane {
@@ -18,8 +19,20 @@ impl ShapeProperties for Plane {
         self.transform = t;
     }
 
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
     fn normal_at(&self, p: Point) -> Vector {
-        return Vector::new(0.0, 1.0, 0.0);
+        self.normal_to_world(Vector::new(0.0, 1.0, 0.0))
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        (self.vector_to_world(Vector::new(1.0, 0.0, 0.0)), self.vector_to_world(Vector::new(0.0, 0.0, 1.0)))
     }
 
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
@@ -27,15 +40,19 @@ impl ShapeProperties for Plane {
 
         match r_t.direction().y().abs() > 0.0001 {
             true => vec![
-                Intersection {
-                    t: -r_t.origin().y() / r_t.direction().y(),
-                    object: self.as_trait()
-                }
+                Intersection::new(-r_t.origin().y() / r_t.direction().y(), self.as_trait())
             ],
             false => vec![]
         }
     }
 
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, 0.0, f64::INFINITY)
+        )
+    }
+
     fn as_trait(&self) -> &dyn ShapeProperties {
         self
     }
@@ -53,6 +70,7 @@ impl ShapeProperties for Plane {
 pub fn plane() -> Plane {
     plane::Plane {
         transform: Transform::new(),
+        parent_transform: Transform::new(),
         material: Material::default()
     }
 }
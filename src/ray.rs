@@ -3,12 +3,34 @@ use crate::tuple::{Point, Vector};
 #[derive(Clone, Copy)]
 pub struct Ray {
     origin: Point,
-    direction: Vector
+    direction: Vector,
+    // in nanometres, only set when rendering spectrally; refraction through a
+    // dispersive material depends on it
+    wavelength: Option<f64>,
+    // set once the ray has come off a rough surface, its own rough hits trace a
+    // single ray instead of branching again
+    scattered: bool
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        Ray {origin, direction}
+        Ray {origin, direction, wavelength: None, scattered: false}
+    }
+
+    pub fn with_wavelength(self, wavelength: Option<f64>) -> Ray {
+        Ray {wavelength, ..self}
+    }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn with_scattered(self, scattered: bool) -> Ray {
+        Ray {scattered, ..self}
+    }
+
+    pub fn scattered(&self) -> bool {
+        self.scattered
     }
 
     pub fn origin(&self) -> Point {
@@ -0,0 +1,1076 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::sphere::Sphere;
+use crate::plane::plane;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::cone::Cone;
+use crate::group::Group;
+use crate::csg::{Csg, Operation};
+use crate::pattern::{Pattern, Texture, UvMapping, solid_pattern, nested_stripe_pattern, nested_gradient_pattern, nested_ring_pattern, nested_checkers_pattern, blend_pattern, perturbed_pattern, marble_pattern, wood_pattern, clouds_pattern, texture_map_pattern, cube_map_pattern};
+use crate::wavefront_obj_parser::{parse_obj_file, parse_obj_mesh_file};
+use crate::light::{Light, AreaLight, DirectionalLight, SpotLight};
+use crate::camera::Sampling;
+use crate::path_tracer::{Integrator, DEFAULT_WHITTED_DEPTH, DEFAULT_PATH_DEPTH};
+use crate::material::{Dispersion, NormalMap, Shading};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+// Scene files follow the YAML dialect of the Ray Tracer Challenge: a list of
+// `add:` items (camera, light, shapes), `define:` macros for materials and
+// transforms, and `extend:` to derive one define from another.
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+fn error<T>(message: String) -> Result<T, SceneError> {
+    Err(SceneError { message })
+}
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: World,
+}
+
+struct SceneParser {
+    defines: HashMap<String, Yaml>,
+    // relative obj paths are resolved against the scene file's directory
+    base_dir: PathBuf,
+}
+
+fn as_number(y: &Yaml) -> Option<f64> {
+    match y {
+        Yaml::Real(_) => y.as_f64(),
+        Yaml::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+fn number(item: &Yaml, key: &str) -> Result<f64, SceneError> {
+    match as_number(&item[key]) {
+        Some(n) => Ok(n),
+        None => error(format!("'{}' must be a number", key)),
+    }
+}
+
+fn optional_number(item: &Yaml, key: &str, default: f64) -> Result<f64, SceneError> {
+    match &item[key] {
+        Yaml::BadValue => Ok(default),
+        _ => number(item, key),
+    }
+}
+
+fn uv_mapping(y: &Yaml) -> Result<UvMapping, SceneError> {
+    match y.as_str() {
+        Some("spherical") => Ok(UvMapping::Spherical),
+        Some("planar") => Ok(UvMapping::Planar),
+        Some("cylindrical") => Ok(UvMapping::Cylindrical),
+        other => error(format!("unknown mapping {:?}", other)),
+    }
+}
+
+// `type: cauchy` with `a` and `b`, or `type: sellmeier` with the three `b` and
+// `c` coefficients of a glass catalogue, wavelengths in micrometres
+fn dispersion(y: &Yaml) -> Result<Dispersion, SceneError> {
+    match y["type"].as_str() {
+        Some("cauchy") => Ok(Dispersion::Cauchy(number(y, "a")?, number(y, "b")?)),
+        Some("sellmeier") => {
+            let (b1, b2, b3) = triple(&y["b"], "b")?;
+            let (c1, c2, c3) = triple(&y["c"], "c")?;
+            Ok(Dispersion::Sellmeier([b1, b2, b3], [c1, c2, c3]))
+        }
+        other => error(format!("unknown dispersion type {:?}", other)),
+    }
+}
+
+fn triple(y: &Yaml, what: &str) -> Result<(f64, f64, f64), SceneError> {
+    if let Some(values) = y.as_vec() {
+        if values.len() == 3 {
+            if let (Some(a), Some(b), Some(c)) = (as_number(&values[0]), as_number(&values[1]), as_number(&values[2])) {
+                return Ok((a, b, c));
+            }
+        }
+    }
+    error(format!("'{}' must be a list of 3 numbers", what))
+}
+
+fn color(y: &Yaml, what: &str) -> Result<Tuple, SceneError> {
+    let (r, g, b) = triple(y, what)?;
+    Ok(Tuple::new(r, g, b, 1.0))
+}
+
+fn point(y: &Yaml, what: &str) -> Result<Point, SceneError> {
+    let (x, y, z) = triple(y, what)?;
+    Ok(Point::new(x, y, z))
+}
+
+fn vector(y: &Yaml, what: &str) -> Result<Vector, SceneError> {
+    let (x, y, z) = triple(y, what)?;
+    Ok(Vector::new(x, y, z))
+}
+
+// later keys win, used by `extend:` and by materials that extend a define
+fn merge_hashes(base: &Yaml, overrides: &Yaml) -> Yaml {
+    match (base, overrides) {
+        (Yaml::Hash(b), Yaml::Hash(o)) => {
+            let mut merged = b.clone();
+            for (k, v) in o {
+                merged.insert(k.clone(), v.clone());
+            }
+            Yaml::Hash(merged)
+        }
+        (_, Yaml::BadValue) => base.clone(),
+        _ => overrides.clone(),
+    }
+}
+
+impl SceneParser {
+    fn define(&mut self, item: &Yaml) -> Result<(), SceneError> {
+        let name = match item["define"].as_str() {
+            Some(n) => n.to_string(),
+            None => return error("'define' needs a name".to_string()),
+        };
+        let mut value = item["value"].clone();
+        if let Some(parent) = item["extend"].as_str() {
+            match self.defines.get(parent) {
+                Some(base) => value = merge_hashes(base, &value),
+                None => return error(format!("define '{}' extends unknown '{}'", name, parent)),
+            }
+        }
+        self.defines.insert(name, value);
+        Ok(())
+    }
+
+    // a material is either the name of a define or a hash of overrides
+    fn material(&self, y: &Yaml) -> Result<Material, SceneError> {
+        let resolved = match y {
+            Yaml::String(name) => match self.defines.get(name) {
+                Some(v) => v.clone(),
+                None => return error(format!("unknown material '{}'", name)),
+            },
+            Yaml::Hash(_) => y.clone(),
+            Yaml::BadValue => return Ok(Material::default()),
+            _ => return error("'material' must be a name or a hash".to_string()),
+        };
+
+        let defaults = Material::default();
+        let c = match &resolved["color"] {
+            Yaml::BadValue => Tuple::new(1.0, 1.0, 1.0, 1.0),
+            other => color(other, "color")?,
+        };
+        let field = |key: &str, default: f64| -> Result<f64, SceneError> {
+            match &resolved[key] {
+                Yaml::BadValue => Ok(default),
+                _ => number(&resolved, key),
+            }
+        };
+
+        let mut m = Material::new(
+            c,
+            field("ambient", defaults.ambient)?,
+            field("diffuse", defaults.diffuse)?,
+            field("specular", defaults.specular)?,
+            field("shininess", defaults.shininess)?,
+        );
+        m.reflective = field("reflective", defaults.reflective)?;
+        m.transparency = field("transparency", defaults.transparency)?;
+        m.refractive_index = field("refractive-index", defaults.refractive_index)?;
+        if let Yaml::Hash(_) = resolved["dispersion"] {
+            m.dispersion = Some(dispersion(&resolved["dispersion"])?);
+        }
+        if !resolved["absorption-color"].is_badvalue() {
+            m.absorption_color = color(&resolved["absorption-color"], "absorption-color")?;
+            m.absorption_density = field("absorption-density", 1.0)?;
+        }
+        // metallic/roughness assets switch the material to the pbr model, a phong
+        // material only uses roughness to blur its reflection and refraction
+        m.shading = match resolved["shading"].as_str() {
+            None if !resolved["metallic"].is_badvalue() => Shading::Pbr,
+            None | Some("phong") => Shading::Phong,
+            Some("pbr") => Shading::Pbr,
+            Some(other) => return error(format!("unknown shading '{}'", other)),
+        };
+        if m.shading == Shading::Pbr {
+            m.metallic = field("metallic", 0.0)?;
+            m.roughness = field("roughness", 0.5)?;
+        } else {
+            m.roughness = field("roughness", defaults.roughness)?;
+        }
+        m.gloss_samples = field("gloss-samples", defaults.gloss_samples as f64)? as usize;
+        m.bump = field("bump", defaults.bump)?;
+        m.bump_scale = field("bump-scale", defaults.bump_scale)?;
+        if let Yaml::Hash(_) = resolved["normal-map"] {
+            m.normal_map = Some(self.normal_map(&resolved["normal-map"])?);
+        }
+        if let Yaml::Hash(_) = resolved["pattern"] {
+            m.set_pattern(&self.pattern(&resolved["pattern"])?);
+        }
+        Ok(m)
+    }
+
+    fn pattern(&self, y: &Yaml) -> Result<Pattern, SceneError> {
+        if y["type"].as_str() == Some("map") {
+            let mut pattern = self.map_pattern(y)?;
+            pattern.set_transform(&self.transform(&y["transform"])?);
+            return Ok(pattern);
+        }
+
+        if y["type"].as_str() == Some("perturbed") {
+            if y["pattern"].as_hash().is_none() {
+                return error("a perturbed pattern needs a 'pattern'".to_string());
+            }
+            let scale = optional_number(y, "scale", 0.2)?;
+            let mut pattern = perturbed_pattern(self.pattern(&y["pattern"])?, scale);
+            pattern.set_transform(&self.transform(&y["transform"])?);
+            return Ok(pattern);
+        }
+
+        let colors = match y["colors"].as_vec() {
+            Some(c) if c.len() == 2 => c,
+            _ => return error("a pattern needs 'colors' with exactly 2 entries".to_string()),
+        };
+        let a = self.sub_pattern(&colors[0])?;
+        let b = self.sub_pattern(&colors[1])?;
+        let mut pattern = match y["type"].as_str() {
+            Some("stripes") => nested_stripe_pattern(a, b),
+            Some("gradient") => nested_gradient_pattern(a, b),
+            Some("rings") => nested_ring_pattern(a, b),
+            Some("checkers") => nested_checkers_pattern(a, b),
+            Some("blend") => blend_pattern(a, b, optional_number(y, "weight", 0.5)?),
+            Some("marble") => marble_pattern(a, b, optional_number(y, "strength", 1.0)?),
+            Some("wood") => wood_pattern(a, b, optional_number(y, "strength", 0.5)?),
+            Some("clouds") => clouds_pattern(a, b),
+            other => return error(format!("unknown pattern type {:?}", other)),
+        };
+        pattern.set_transform(&self.transform(&y["transform"])?);
+        Ok(pattern)
+    }
+
+    // an entry of 'colors' is either a plain color or a whole pattern
+    fn sub_pattern(&self, y: &Yaml) -> Result<Pattern, SceneError> {
+        match y.as_hash() {
+            Some(_) => self.pattern(y),
+            None => Ok(solid_pattern(color(y, "colors")?)),
+        }
+    }
+
+    // `mapping: cube` takes one `uv_pattern`-style image per face, every other
+    // mapping a single `uv_pattern`
+    fn map_pattern(&self, y: &Yaml) -> Result<Pattern, SceneError> {
+        let mapping = match y["mapping"].as_str() {
+            Some("cube") => {
+                let face = |name: &str| self.uv_image(&y[name], name);
+                return Ok(cube_map_pattern([
+                    face("left")?,
+                    face("front")?,
+                    face("right")?,
+                    face("back")?,
+                    face("up")?,
+                    face("down")?,
+                ]));
+            }
+            _ => uv_mapping(&y["mapping"])?,
+        };
+        Ok(texture_map_pattern(self.uv_image(&y["uv_pattern"], "uv_pattern")?, mapping))
+    }
+
+    // images are stored sRGB encoded, shading works on linear colors
+    fn uv_image(&self, y: &Yaml, what: &str) -> Result<Canvas, SceneError> {
+        let mut image = self.image(y, what)?;
+        image.linearize();
+        Ok(image)
+    }
+
+    fn image(&self, y: &Yaml, what: &str) -> Result<Canvas, SceneError> {
+        if y["type"].as_str() != Some("image") {
+            return error(format!("'{}' must be an image with a 'file'", what));
+        }
+        let file = match y["file"].as_str() {
+            Some(f) => self.base_dir.join(f),
+            None => return error(format!("'{}' needs a 'file'", what)),
+        };
+        match Canvas::load(&file.to_string_lossy()) {
+            // texture lookups index up to width - 1 and height - 1
+            Ok(image) if image.width() == 0 || image.height() == 0 => error(format!("{}: '{}' is an empty image", file.display(), what)),
+            Ok(image) => Ok(image),
+            Err(e) => error(e.to_string()),
+        }
+    }
+
+    // heights come from a pattern, normals from an image which is used as is,
+    // its colours encode directions rather than light
+    fn normal_map(&self, y: &Yaml) -> Result<NormalMap, SceneError> {
+        match y["type"].as_str() {
+            Some("bump") => {
+                if y["pattern"].as_hash().is_none() {
+                    return error("a bump map needs a 'pattern'".to_string());
+                }
+                Ok(NormalMap::Bump(self.pattern(&y["pattern"])?, optional_number(y, "height", 1.0)?))
+            }
+            Some("image") => {
+                let mapping = uv_mapping(&y["mapping"])?;
+                Ok(NormalMap::Image(Texture::new(self.image(y, "normal-map")?), mapping))
+            }
+            other => error(format!("unknown normal-map type {:?}", other)),
+        }
+    }
+
+    // operations are applied in the order they are listed
+    fn transform(&self, y: &Yaml) -> Result<Transform, SceneError> {
+        let mut t = Transform::new();
+        self.apply_transforms(y, &mut t)?;
+        Ok(t)
+    }
+
+    fn apply_transforms(&self, y: &Yaml, t: &mut Transform) -> Result<(), SceneError> {
+        let ops = match y {
+            Yaml::Array(ops) => ops,
+            Yaml::BadValue => return Ok(()),
+            _ => return error("'transform' must be a list".to_string()),
+        };
+
+        for op in ops {
+            if let Yaml::String(name) = op {
+                match self.defines.get(name) {
+                    Some(v) => self.apply_transforms(v, t)?,
+                    None => return error(format!("unknown transform '{}'", name)),
+                }
+                continue;
+            }
+
+            let parts = match op.as_vec() {
+                Some(p) if !p.is_empty() => p,
+                _ => return error("a transform must be a non empty list".to_string()),
+            };
+            let args: Vec<f64> = parts[1..].iter().filter_map(as_number).collect();
+            let arity = |n: usize| -> Result<(), SceneError> {
+                if args.len() != n {
+                    return error(format!("{:?} expects {} numbers", parts[0].as_str(), n));
+                }
+                Ok(())
+            };
+            *t = match parts[0].as_str() {
+                Some("translate") => { arity(3)?; t.translate(args[0], args[1], args[2]) }
+                Some("scale") => { arity(3)?; t.scaling(args[0], args[1], args[2]) }
+                Some("rotate-x") => { arity(1)?; t.rotate_x(args[0]) }
+                Some("rotate-y") => { arity(1)?; t.rotate_y(args[0]) }
+                Some("rotate-z") => { arity(1)?; t.rotate_z(args[0]) }
+                Some("shear") => { arity(6)?; t.shear(args[0], args[1], args[2], args[3], args[4], args[5]) }
+                other => return error(format!("unknown transform {:?}", other)),
+            };
+        }
+        Ok(())
+    }
+
+    fn shape(&self, item: &Yaml) -> Result<Box<dyn ShapeProperties>, SceneError> {
+        let kind = item["add"].as_str().unwrap_or("");
+        let mut shape: Box<dyn ShapeProperties> = match kind {
+            "sphere" => Box::new(Sphere::default()),
+            "plane" => Box::new(plane()),
+            "cube" => Box::new(Cube::default()),
+            "cylinder" => {
+                let mut c = Cylinder::new();
+                let (min, max) = self.truncation(item)?;
+                c.set_cylinder_truncation(min, max);
+                c.set_cylinder_closed(item["closed"].as_bool().unwrap_or(false));
+                Box::new(c)
+            }
+            "cone" => {
+                let mut c = Cone::new();
+                let (min, max) = self.truncation(item)?;
+                c.set_cylinder_truncation(min, max);
+                c.set_cylinder_closed(item["closed"].as_bool().unwrap_or(false));
+                Box::new(c)
+            }
+            "group" => {
+                let mut g = Group::new();
+                if let Some(children) = item["children"].as_vec() {
+                    for child in children {
+                        if child["material"].is_badvalue() {
+                            g.add_child(self.shape(child)?);
+                        } else {
+                            g.add_child_keeping_material(self.shape(child)?);
+                        }
+                    }
+                }
+                Box::new(g)
+            }
+            "csg" => {
+                let operation = match item["operation"].as_str() {
+                    Some("union") => Operation::Union,
+                    Some("intersection") => Operation::Intersection,
+                    Some("difference") => Operation::Difference,
+                    other => return error(format!("unknown csg operation {:?}", other)),
+                };
+                if item["left"].as_hash().is_none() || item["right"].as_hash().is_none() {
+                    return error("a csg needs a 'left' and a 'right' shape".to_string());
+                }
+                let mut c = Csg::new(operation, self.shape(&item["left"])?, self.shape(&item["right"])?);
+                c.keep_child_materials(!item["left"]["material"].is_badvalue(), !item["right"]["material"].is_badvalue());
+                Box::new(c)
+            }
+            "obj" => {
+                let file = match item["file"].as_str() {
+                    Some(f) => self.base_dir.join(f),
+                    None => return error("'obj' needs a 'file'".to_string()),
+                };
+                match parse_obj_file(&file.to_string_lossy()) {
+                    Ok(parser) => Box::new(parser.into_group()),
+                    Err(e) => return error(format!("{}: {}", file.display(), e)),
+                }
+            }
+            // an obj file loaded into shared buffers, for big models
+            "mesh" => {
+                let file = match item["file"].as_str() {
+                    Some(f) => self.base_dir.join(f),
+                    None => return error("'mesh' needs a 'file'".to_string()),
+                };
+                match parse_obj_mesh_file(&file.to_string_lossy()) {
+                    Ok(mesh) => Box::new(mesh),
+                    Err(e) => return error(format!("{}: {}", file.display(), e)),
+                }
+            }
+            other => return error(format!("unknown item '{}'", other)),
+        };
+
+        shape.set_transform(self.transform(&item["transform"])?);
+        // groups and csgs keep their children's materials unless one is given explicitly,
+        // and then only pass it on to children without a material of their own
+        let composite = kind == "group" || kind == "obj" || kind == "csg";
+        if !composite || !item["material"].is_badvalue() {
+            shape.set_material(self.material(&item["material"])?);
+        }
+        Ok(shape)
+    }
+
+    fn truncation(&self, item: &Yaml) -> Result<(f64, f64), SceneError> {
+        let min = if item["min"].is_badvalue() { f64::MIN } else { number(item, "min")? };
+        let max = if item["max"].is_badvalue() { f64::MAX } else { number(item, "max")? };
+        Ok((min, max))
+    }
+
+    // `type` is one of point, area, spot or directional; without it a light
+    // with a `corner` is an area light and anything else a point light
+    fn light(&self, item: &Yaml) -> Result<Box<dyn Light>, SceneError> {
+        let intensity = color(&item["intensity"], "intensity")?;
+        let kind = match item["type"].as_str() {
+            Some(kind) => kind,
+            None if !item["corner"].is_badvalue() => "area",
+            None => "point",
+        };
+
+        match kind {
+            "point" => Ok(Box::new(PointLight::new(point(&item["at"], "at")?, intensity))),
+            "area" => {
+                let mut light = AreaLight::new(
+                    point(&item["corner"], "corner")?,
+                    vector(&item["uvec"], "uvec")?,
+                    number(item, "usteps")? as usize,
+                    vector(&item["vvec"], "vvec")?,
+                    number(item, "vsteps")? as usize,
+                    intensity
+                );
+                light.jitter = item["jitter"].as_bool().unwrap_or(false);
+                Ok(Box::new(light))
+            }
+            "directional" => Ok(Box::new(DirectionalLight::new(vector(&item["direction"], "direction")?, intensity))),
+            "spot" => Ok(Box::new(SpotLight::new(
+                point(&item["at"], "at")?,
+                vector(&item["direction"], "direction")?,
+                number(item, "inner-angle")?,
+                number(item, "outer-angle")?,
+                intensity
+            ))),
+            other => error(format!("unknown light type '{}'", other)),
+        }
+    }
+
+    fn camera(&self, item: &Yaml) -> Result<Camera, SceneError> {
+        let mut camera = Camera::new(number(item, "width")?, number(item, "height")?, number(item, "field-of-view")?);
+        camera.set_transform(ViewTransform(
+            point(&item["from"], "from")?,
+            point(&item["to"], "to")?,
+            vector(&item["up"], "up")?,
+        ));
+
+        // optional supersampling, e.g. `sampling: jittered` with `samples: 16`
+        let samples = if item["samples"].is_badvalue() { 1 } else { number(item, "samples")? as usize };
+        let sampling = match item["sampling"].as_str() {
+            None | Some("grid") => Sampling::Grid,
+            Some("jittered") => Sampling::Jittered,
+            Some("adaptive") => {
+                let threshold = if item["adaptive-threshold"].is_badvalue() { 0.05 } else { number(item, "adaptive-threshold")? };
+                Sampling::Adaptive(threshold)
+            }
+            Some(other) => return error(format!("unknown sampling '{}'", other)),
+        };
+        camera.set_sampling(sampling, samples);
+
+        // depth of field, focused on `to` unless a focal distance is given
+        if !item["aperture"].is_badvalue() {
+            let focal_distance = if item["focal-distance"].is_badvalue() {
+                (point(&item["to"], "to")? - point(&item["from"], "from")?).mag()
+            } else {
+                number(item, "focal-distance")?
+            };
+            camera.set_lens(number(item, "aperture")?, focal_distance);
+        }
+
+        // `integrator: path` for global illumination, `max-depth` bounds the bounces of either
+        let integrator = match item["integrator"].as_str() {
+            None | Some("whitted") => Integrator::Whitted { max_depth: optional_number(item, "max-depth", DEFAULT_WHITTED_DEPTH as f64)? as u32 },
+            Some("path") => Integrator::PathTraced { max_depth: optional_number(item, "max-depth", DEFAULT_PATH_DEPTH as f64)? as u32 },
+            Some(other) => return error(format!("unknown integrator '{}'", other)),
+        };
+        camera.set_integrator(integrator);
+        camera.set_spectral(item["spectral"].as_bool().unwrap_or(false));
+        Ok(camera)
+    }
+}
+
+pub fn parse_scene(content: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let docs = match YamlLoader::load_from_str(content) {
+        Ok(d) => d,
+        Err(e) => return error(format!("invalid yaml: {}", e)),
+    };
+    let items = match docs.first().and_then(|d| d.as_vec()) {
+        Some(items) => items,
+        None => return error("a scene must be a list of items".to_string()),
+    };
+
+    let mut parser = SceneParser { defines: HashMap::new(), base_dir: base_dir.to_path_buf() };
+    let mut camera: Option<Camera> = None;
+    let mut world = World::new();
+    world.objects_mut().clear();
+    world.lights.clear();
+
+    for item in items {
+        if !item["define"].is_badvalue() {
+            parser.define(item)?;
+            continue;
+        }
+        match item["add"].as_str() {
+            Some("camera") => camera = Some(parser.camera(item)?),
+            Some("light") => world.lights.push(parser.light(item)?),
+            Some(_) => world.add_object(parser.shape(item)?),
+            None => return error("every item needs 'add' or 'define'".to_string()),
+        }
+    }
+
+    let camera = match camera {
+        Some(c) => c,
+        None => return error("the scene has no camera".to_string()),
+    };
+    world.build_bvh();
+    Ok(Scene { camera, world })
+}
+
+pub fn load_scene(path: &str) -> Result<Scene, SceneError> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return error(format!("cannot read {}: {}", path, e)),
+    };
+    let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    parse_scene(&content, base_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::float_eq;
+    use crate::intersection::Intersection;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    const SCENE: &str = "
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+
+- add: light
+  at: [ -10, 10, -10 ]
+  intensity: [ 1, 1, 1 ]
+
+- define: white-material
+  value:
+    color: [ 1, 1, 1 ]
+    diffuse: 0.7
+    ambient: 0.1
+
+- define: blue-material
+  extend: white-material
+  value:
+    color: [ 0.537, 0.831, 0.914 ]
+    reflective: 0.5
+
+- define: standard-transform
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5, 0.5, 0.5 ]
+
+- add: sphere
+  material: blue-material
+  transform:
+    - standard-transform
+    - [ translate, 4, 0, 0 ]
+
+- add: plane
+  material:
+    color: [ 1, 0, 0 ]
+    pattern:
+      type: checkers
+      colors:
+        - [ 1, 1, 1 ]
+        - [ 0, 0, 0 ]
+
+- add: cylinder
+  min: 0
+  max: 1
+  closed: true
+";
+
+    #[test]
+    fn parsing_a_scene_file() {
+        let scene = parse_scene(SCENE, Path::new(".")).unwrap();
+        assert_eq!(scene.world.objects().len(), 3);
+        assert_eq!(scene.world.lights.len(), 1);
+        assert_eq!(scene.camera.integrator(), Integrator::Whitted { max_depth: DEFAULT_WHITTED_DEPTH });
+        let sample = scene.world.lights[0].samples(Point::new(-10.0, 0.0, -10.0))[0];
+        assert!(float_eq(sample.distance, 10.0));
+
+        let sphere = &scene.world.objects()[0];
+        let m = sphere.material();
+        assert!(float_eq(m.diffuse, 0.7));
+        assert!(float_eq(m.reflective, 0.5));
+        let expected = Transform::new().translate(1.0, -1.0, 1.0).scaling(0.5, 0.5, 0.5).translate(4.0, 0.0, 0.0);
+        assert!(sphere.transform() == expected);
+    }
+
+    #[test]
+    fn max_depth_applies_to_the_whitted_integrator() {
+        let scene = SCENE.replace("  up: [ 0, 1, 0 ]\n", "  up: [ 0, 1, 0 ]\n  max-depth: 2\n");
+        let scene = parse_scene(&scene, Path::new(".")).unwrap();
+        assert_eq!(scene.camera.integrator(), Integrator::Whitted { max_depth: 2 });
+    }
+
+    #[test]
+    fn parsing_area_directional_and_spot_lights() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+  sampling: adaptive
+  samples: 16
+  integrator: path
+  max-depth: 5
+- add: light
+  corner: [ -1, 2, 4 ]
+  uvec: [ 2, 0, 0 ]
+  vvec: [ 0, 2, 0 ]
+  usteps: 10
+  vsteps: 10
+  jitter: true
+  intensity: [ 1.5, 1.5, 1.5 ]
+- add: light
+  type: directional
+  direction: [ 0, -1, 0 ]
+  intensity: [ 1, 1, 1 ]
+- add: light
+  type: spot
+  at: [ 0, 5, 0 ]
+  direction: [ 0, -1, 0 ]
+  inner-angle: 0.3
+  outer-angle: 0.5
+  intensity: [ 1, 1, 1 ]
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        assert_eq!(scene.world.lights[0].samples(Point::new(0.0, 0.0, 0.0)).len(), 100);
+        assert_eq!(scene.camera.sampling(), Sampling::Adaptive(0.05));
+        assert_eq!(scene.camera.samples_per_pixel(), 16);
+        assert_eq!(scene.camera.integrator(), Integrator::PathTraced { max_depth: 5 });
+        assert!(scene.world.lights[1].samples(Point::new(0.0, 0.0, 0.0))[0].distance.is_infinite());
+        assert!(float_eq(scene.world.lights[2].attenuation(Point::new(0.0, 5.0, 5.0)), 0.0));
+    }
+
+    #[test]
+    fn parsing_an_image_texture() {
+        let dir = std::env::temp_dir();
+        let mut image = Canvas::new(2, 2);
+        image.write_pixel(0, 0, Tuple::new(1.0, 1.0, 1.0, 0.0));
+        image.save(dir.join("scene_texture.ppm").to_str().unwrap()).unwrap();
+
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: sphere
+  material:
+    pattern:
+      type: map
+      mapping: spherical
+      uv_pattern:
+        type: image
+        file: scene_texture.ppm
+";
+        let scene = parse_scene(scene, &dir).unwrap();
+        let sphere = &scene.world.objects()[0];
+        // the pole samples halfway along the top row, between white and black
+        let pole = sphere.material().color_at_object(sphere.as_trait(), Point::new(0.0, 1.0, 0.0));
+        assert!(float_eq(pole.x, 0.5));
+
+        let missing = "- add: sphere\n  material:\n    pattern:\n      type: map\n      mapping: planar\n      uv_pattern:\n        type: image\n        file: nope.ppm\n";
+        assert!(parse_scene(missing, &dir).is_err());
+        let _ = fs::remove_file(dir.join("scene_texture.ppm"));
+    }
+
+    #[test]
+    fn an_empty_texture_image_is_an_error() {
+        let dir = std::env::temp_dir();
+        fs::write(dir.join("scene_empty_texture.ppm"), "P3\n0 0\n255\n").unwrap();
+        let scene = "- add: sphere\n  material:\n    pattern:\n      type: map\n      mapping: planar\n      uv_pattern:\n        type: image\n        file: scene_empty_texture.ppm\n";
+        assert!(parse_scene(scene, &dir).is_err());
+        let _ = fs::remove_file(dir.join("scene_empty_texture.ppm"));
+    }
+
+    #[test]
+    fn parsing_nested_and_noise_patterns() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: plane
+  material:
+    pattern:
+      type: stripes
+      colors:
+        - type: checkers
+          colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ]
+          transform:
+            - [ scale, 0.5, 0.5, 0.5 ]
+        - type: blend
+          weight: 0.25
+          colors: [ [ 1, 0, 0 ], [ 0, 0, 1 ] ]
+- add: sphere
+  material:
+    bump: 0.3
+    bump-scale: 0.1
+    pattern:
+      type: perturbed
+      scale: 0.1
+      pattern:
+        type: marble
+        strength: 2
+        colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ]
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        let plane = &scene.world.objects()[0];
+        let color = |x| plane.material().color_at_object(plane.as_trait(), Point::new(x, 0.0, 0.0));
+        let rgb_eq = |c: Tuple, r, g, b| float_eq(c.x, r) && float_eq(c.y, g) && float_eq(c.z, b);
+        assert!(rgb_eq(color(0.25), 1.0, 1.0, 1.0));
+        assert!(rgb_eq(color(0.75), 0.0, 0.0, 0.0));
+        assert!(rgb_eq(color(1.5), 0.75, 0.0, 0.25));
+
+        let sphere = scene.world.objects()[1].material();
+        assert!(float_eq(sphere.bump, 0.3));
+        assert!(float_eq(sphere.bump_scale, 0.1));
+
+        let missing = "- add: sphere\n  material:\n    pattern:\n      type: perturbed\n";
+        let err = parse_scene(missing, Path::new(".")).err().unwrap();
+        assert_eq!(err.message, "a perturbed pattern needs a 'pattern'");
+    }
+
+    #[test]
+    fn parsing_pbr_materials() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: sphere
+  material:
+    color: [ 1, 0.8, 0.3 ]
+    metallic: 1
+    roughness: 0.25
+- add: sphere
+  material:
+    shading: pbr
+    roughness: 0.6
+    refractive-index: 1.45
+- add: sphere
+  material:
+    roughness: 0.3
+    gloss-samples: 16
+    reflective: 0.8
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        let gold = scene.world.objects()[0].material();
+        assert_eq!(gold.shading, Shading::Pbr);
+        assert!(float_eq(gold.metallic, 1.0) && float_eq(gold.roughness, 0.25));
+        let plastic = scene.world.objects()[1].material();
+        assert_eq!(plastic.shading, Shading::Pbr);
+        assert!(float_eq(plastic.metallic, 0.0) && float_eq(plastic.refractive_index, 1.45));
+        // roughness alone blurs the reflection of a phong material
+        let brushed = scene.world.objects()[2].material();
+        assert_eq!(brushed.shading, Shading::Phong);
+        assert!(float_eq(brushed.roughness, 0.3) && brushed.gloss_samples == 16);
+    }
+
+    #[test]
+    fn parsing_coloured_glass() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: sphere
+  material:
+    transparency: 1
+    refractive-index: 1.5
+    absorption-color: [ 0.2, 0.8, 0.4 ]
+    absorption-density: 2
+- add: sphere
+  material:
+    transparency: 1
+    absorption-color: [ 0.5, 0.5, 0.5 ]
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        let green = scene.world.objects()[0].material();
+        assert!(float_eq(green.absorption_color.y, 0.8) && float_eq(green.absorption_density, 2.0));
+        // half a unit at density 2 is one application of the colour
+        assert!(float_eq(green.transmittance(0.5).x, 0.2));
+        assert!(float_eq(scene.world.objects()[1].material().absorption_density, 1.0));
+    }
+
+    #[test]
+    fn parsing_dispersive_glass_and_a_spectral_camera() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+  spectral: true
+- add: sphere
+  material:
+    transparency: 1
+    refractive-index: 1.5168
+    dispersion:
+      type: sellmeier
+      b: [ 1.03961212, 0.231792344, 1.01046945 ]
+      c: [ 0.00600069867, 0.0200179144, 103.560653 ]
+- add: sphere
+  material:
+    dispersion:
+      type: cauchy
+      a: 1.5046
+      b: 0.0042
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        assert!(scene.camera.spectral());
+        // bk7 at the sodium d line
+        let bk7 = scene.world.objects()[0].material();
+        assert!(f64::abs(bk7.refractive_index_at(Some(587.6)) - 1.5168) < 0.0001);
+        let cauchy = scene.world.objects()[1].material();
+        assert!(f64::abs(cauchy.refractive_index_at(Some(500.0)) - 1.5214) < 0.0001);
+
+        let unknown = "- add: sphere\n  material:\n    dispersion:\n      type: abbe\n";
+        let err = parse_scene(unknown, Path::new(".")).err().unwrap();
+        assert_eq!(err.message, "unknown dispersion type Some(\"abbe\")");
+    }
+
+    #[test]
+    fn parsing_normal_maps() {
+        let dir = std::env::temp_dir();
+        fs::write(dir.join("scene_normals.ppm"), "P3\n1 1\n255\n255 128 255\n").unwrap();
+
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: plane
+  material:
+    normal-map:
+      type: image
+      mapping: planar
+      file: scene_normals.ppm
+- add: sphere
+  material:
+    normal-map:
+      type: bump
+      height: 0.2
+      pattern:
+        type: clouds
+        colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ]
+";
+        let scene = parse_scene(scene, &dir).unwrap();
+        let plane = &scene.world.objects()[0];
+        // normal maps are not sRGB decoded, 128 stays the middle of the range
+        let hit = Intersection::new(1.0, plane.as_trait());
+        let n = plane.material().shading_normal(&hit, Point::new(0.2, 0.0, 0.3), Vector::new(0.0, 1.0, 0.0));
+        assert!(f64::abs(n.x() - FRAC_1_SQRT_2) < 0.01 && f64::abs(n.y() - FRAC_1_SQRT_2) < 0.01 && f64::abs(n.z()) < 0.01);
+        match scene.world.objects()[1].material().normal_map {
+            Some(NormalMap::Bump(_, height)) => assert!(float_eq(height, 0.2)),
+            _ => panic!("expected a bump map"),
+        }
+
+        let unknown = "- add: sphere\n  material:\n    normal-map:\n      type: displacement\n";
+        let err = parse_scene(unknown, &dir).err().unwrap();
+        assert_eq!(err.message, "unknown normal-map type Some(\"displacement\")");
+    }
+
+    #[test]
+    fn a_group_material_skips_children_with_their_own() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: group
+  material:
+    color: [ 0, 0, 1 ]
+  children:
+    - add: sphere
+      transform:
+        - [ translate, -2, 0, 0 ]
+    - add: sphere
+      material:
+        color: [ 1, 0, 0 ]
+      transform:
+        - [ translate, 2, 0, 0 ]
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        let g = &scene.world.objects()[0];
+        for (x, red, blue) in [(-2.0, 0.0, 1.0), (2.0, 1.0, 0.0)] {
+            let r = Ray::new(Point::new(x, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+            let xs = g.intersect(&r);
+            let color = xs[0].object.material().color_at_object(xs[0].object, Point::new(x, 0.0, -1.0));
+            assert!(float_eq(color.x, red) && float_eq(color.z, blue));
+        }
+    }
+
+    #[test]
+    fn parsing_a_csg() {
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: csg
+  operation: difference
+  transform:
+    - [ translate, 0, 1, 0 ]
+  left:
+    add: cube
+    material:
+      color: [ 1, 0, 0 ]
+  right:
+    add: cylinder
+    min: -2
+    max: 2
+    closed: true
+    transform:
+      - [ scale, 0.5, 1, 0.5 ]
+";
+        let scene = parse_scene(scene, Path::new(".")).unwrap();
+        assert_eq!(scene.world.objects().len(), 1);
+        let part = &scene.world.objects()[0];
+        // straight down the drilled hole
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(part.intersect(&r).is_empty());
+        let r = Ray::new(Point::new(0.75, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let xs = part.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(float_eq(xs[0].t, 3.0));
+        assert!(float_eq(xs[0].object.material().color_at_object(xs[0].object, Point::new(0.75, 2.0, 0.0)).x, 1.0));
+
+        let bad = "- add: csg\n  operation: xor\n";
+        let err = parse_scene(bad, Path::new(".")).err().unwrap();
+        assert_eq!(err.message, "unknown csg operation Some(\"xor\")");
+    }
+
+    #[test]
+    fn parsing_a_mesh() {
+        let dir = std::env::temp_dir();
+        fs::write(dir.join("scene_mesh.obj"), "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let scene = "
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 0.785
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+- add: mesh
+  file: scene_mesh.obj
+  material:
+    diffuse: 0.3
+";
+        let scene = parse_scene(scene, &dir).unwrap();
+        let mesh = &scene.world.objects()[0];
+        assert!(float_eq(mesh.material().diffuse, 0.3));
+        let r = Ray::new(Point::new(0.25, 0.75, -1.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = mesh.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].index, 1);
+
+        let missing = "- add: mesh\n  file: nope.obj\n";
+        assert!(parse_scene(missing, &dir).is_err());
+    }
+
+    #[test]
+    fn a_scene_without_a_camera_is_rejected() {
+        let err = parse_scene("- add: sphere\n", Path::new(".")).err().unwrap();
+        assert_eq!(err.message, "the scene has no camera");
+    }
+
+    #[test]
+    fn unknown_defines_are_reported() {
+        let scene = "- add: sphere\n  material: nothing\n";
+        let err = parse_scene(scene, Path::new(".")).err().unwrap();
+        assert_eq!(err.message, "unknown material 'nothing'");
+    }
+}
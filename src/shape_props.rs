@@ -1,25 +1,38 @@
 use crate::*;
 use crate::intersection::Intersection;
-use std::rc::Rc;
+use crate::bounds::BoundingBox;
 use std::mem::swap;
 use crate::sphere::Sphere;
 use std::borrow::Borrow;
 
-// pub struct Shape {
-//     primitives: Vec<Rc<ShapeNode>>,
-// }
-
-// add group  - shape
-//    new empty node with transform
-//         shape node
-
-pub trait ShapeProperties {
+// shapes are shared read-only between render threads
+pub trait ShapeProperties: Send + Sync {
     fn transform(&self) -> Transform;
 
     fn set_transform(&mut self, t: Transform);
 
+    // accumulated transform of all the groups enclosing this shape,
+    // identity for a shape that sits directly in the world
+    fn parent_transform(&self) -> Transform;
+
+    fn set_parent_transform(&mut self, t: Transform);
+
     fn normal_at(&self, p: Point) -> Vector;
 
+    // shapes that interpolate their normal (smooth triangles) need the hit's u/v
+    fn normal_at_hit(&self, p: Point, _hit: &Intersection) -> Vector {
+        self.normal_at(p)
+    }
+
+    // world space directions in which the texture u and v grow at p, the frame used by
+    // normal and bump maps; they need not be unit length or orthogonal to the normal
+    fn tangents_at(&self, p: Point) -> (Vector, Vector);
+
+    // meshes need the hit to know which triangle the tangents belong to
+    fn tangents_at_hit(&self, p: Point, _hit: &Intersection) -> (Vector, Vector) {
+        self.tangents_at(p)
+    }
+
     fn intersect(&self, ray: &Ray) -> Vec<Intersection>;
 
     fn as_trait(&self) -> &dyn ShapeProperties;
@@ -27,13 +40,53 @@ pub trait ShapeProperties {
     fn material(&self) -> Material;
 
     fn set_material(&mut self, m: Material);
-    // fn add_object(&mut self, obj: Rc<shape::Shape>) -> NodeId;
-    //
-    // fn add_group(&mut self, obj: Rc<Shape>) -> GroupId;
-    // fn world_to_object(&self, )
+
+    // bounding box in object space
+    fn local_bounds(&self) -> BoundingBox;
+
+    // bounding box in the space of whoever holds the shape (world or group)
+    fn bounds(&self) -> BoundingBox {
+        self.local_bounds().transform(&self.transform())
+    }
+
+    // whether shape is this shape or one of its descendants
+    fn includes(&self, shape: &dyn ShapeProperties) -> bool {
+        std::ptr::eq(self.as_trait() as *const dyn ShapeProperties as *const u8, shape as *const dyn ShapeProperties as *const u8)
+    }
+
+    // only shapes with children have anything to build
+    fn build_bvh(&mut self) {}
+
+    fn world_to_object(&self, p: Point) -> Point {
+        let world_transform = self.parent_transform() * self.transform();
+        world_transform.inverse() * p
+    }
+
+    fn vector_to_world(&self, v: Vector) -> Vector {
+        let world_transform = self.parent_transform() * self.transform();
+        let mut world_vector = world_transform * v;
+        world_vector.data.w = 0.0;
+        world_vector
+    }
+
+    fn normal_to_world(&self, n: Vector) -> Vector {
+        let world_transform = self.parent_transform() * self.transform();
+        let mut world_normal = world_transform.inverse().transpose() * n;
+        world_normal.data.w = 0.0;
+        world_normal.normalize()
+    }
 }
 
 
+// tangents of the surfaces of revolution around y: u runs around the axis, v up along it
+pub(crate) fn tangents_around_y(local_point: Point) -> (Vector, Vector) {
+    let tangent = match local_point.x() == 0.0 && local_point.z() == 0.0 {
+        true => Vector::new(1.0, 0.0, 0.0),
+        false => Vector::new(-local_point.z(), 0.0, local_point.x()),
+    };
+    (tangent, Vector::new(0.0, 1.0, 0.0))
+}
+
 pub(crate) fn check_cap_cone(ray: &Ray, t: f64, y: f64) -> bool {
     let x = ray.origin().x() + t * ray.direction().x();
     let z = ray.origin().z() + t * ray.direction().z();
@@ -87,156 +140,3 @@ pub fn hit(ts: Vec<Intersection>) -> Option<Intersection> {
     return None;
 }
 
-//
-// pub struct ShapeNode {
-//     transform: Option<Transform>,
-//     parent: Option<NodeId>,
-//     // previous_sibling: Option<NodeId>,
-//     // next_sibling: Option<NodeId>,
-//     // first_child: Option<NodeId>,
-//     // last_child: Option<NodeId>,
-//
-//     /// The actual data which will be stored within the tree
-//     pub data: Option<shape::Shape>,
-//     pub id: Option<NodeId>
-// }
-//
-// pub struct NodeId {
-//     index: usize,
-// }
-//
-// pub struct GroupId {
-//     index: usize
-// }
-//
-// impl ShapeProperties for Shape {
-//     fn transform(&self) -> Transform {
-//         self.transform.clone()
-//     }
-//
-//     fn set_transform(&mut self, t: Transform) {
-//         self.transform = t;
-//     }
-//
-//     fn normal_at(&self, p: Point) -> Vector {
-//         unimplemented!()
-//     }
-//
-//     fn intersect(&mut self, ray: &Ray) -> Vec<Intersection> {
-//         let mut result = vec![];
-//
-//         for object in &mut self.primitives {
-//             for t in object.data.kind.intersections(&ray,
-//                                                     self.transform.clone() * object.data.transform.clone(),
-//                                                     object.data.cylinder_minimum,
-//                                                     object.data.cylinder_maximum,
-//                                                     object.data.cylinder_closed) {
-//                 result.push(
-//                     Intersection {
-//                         t,
-//                         object: object.data.unwrap()
-//                     }
-//                 )
-//             }
-//         }
-//
-//         return result;
-//     }
-//
-//     fn add_object(&mut self, obj: Rc<ShapeNode>) -> NodeId {
-//         let next_index = self.primitives.len();
-//
-//         obj.parent = Some(NodeId {index: 0});
-//         obj.id = Some(NodeId {index: next_index});
-//         self.primitives.push(
-//             obj
-//         );
-//
-//         return NodeId { index: next_index };
-//     }
-//
-//
-//     fn add_group(&mut self, group: Rc<shape_props::Shape>) -> NodeId {
-//         let next_index = self.primitives.len();
-//
-//         group.primitives[0].parent = Some(NodeId {index: 0});
-//         self.primitives.push(
-//             group.primitives[0].
-//         );
-//
-//         for obj in &group.primitives {
-//             let next_index = self.primitives.len();
-//
-//         }
-//         return NodeId {index: next_index};
-//     }
-// }
-//
-// pub fn group() -> Shape {
-//     Shape {primitives: vec![
-//         ShapeNode {
-//             transform: Some(Transform::new()),
-//             parent: None,
-//             data: None
-//         }
-//     ]}
-//
-// }
-//
-// mod tests {
-//     use super::*;
-//
-//
-//     #[test]
-//     fn intersecting_a_ray_with_an_empty_group() {
-//         let mut s = group();
-//         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
-//         let xs = s.intersect(&r);
-//         assert_eq!(xs.len(), 0);
-//     }
-//
-//     #[test]
-//     fn intersecting_a_ray_with_a_nonempty_group() {
-//         let mut g = group();
-//         let mut s1 = shape::Shape::sphere();
-//         let mut s2 = shape::Shape::sphere();
-//         s2.set_transform(Transform::new().translate(0.0, 0.0, -3.0));
-//         let mut s3 = shape::Shape::sphere();
-//         s3.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
-//
-//         g.add_object(s1);
-//         g.add_object(s2);
-//         g.add_object(s3);
-//
-//         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
-//
-//         let xs = g.intersect(&r);
-//         assert_eq!(xs.len(), 4);
-//     }
-//
-//     #[test]
-//     fn intersecting_a_transformed_group() {
-//         let mut g = group();
-//         g.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
-//         let mut s = shape::Shape::sphere();
-//         s.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
-//         g.add_object(s);
-//
-//         let r = Ray::new(Point::new(10.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
-//
-//         let xs = g.intersect(&r);
-//         assert_eq!(xs.len(), 2);
-//     }
-//
-//     #[test]
-//     fn converting_a_point_from_world_to_object_space() {
-//         let mut g1 = group();
-//         g1.set_transform(Transform::new().rotate_y(PI / 2.0));
-//         let mut g2 = group();
-//         g2.set_transform(Transform::new().scaling(2.0, 2.0, 2.0));
-//         let mut s = shape::Shape::sphere();
-//         s.set_transform(Transform::new().translate(5.0, 0.0, 0.0));
-//
-//         g2.add_object(s);
-//     }
-// }
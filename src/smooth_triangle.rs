@@ -0,0 +1,177 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
+use crate::intersection::Intersection;
+use crate::triangle::triangle_intersection;
+
+// a triangle with one normal per vertex, the normal at a hit is interpolated
+// from the barycentric u/v stored on the intersection
+#[derive(Clone)]
+pub struct SmoothTriangle {
+    pub material: Material,
+    pub transform: Transform,
+    pub parent_transform: Transform,
+    pub p1: Point,
+    pub p2: Point,
+    pub p3: Point,
+    pub n1: Vector,
+    pub n2: Vector,
+    pub n3: Vector,
+    pub e1: Vector,
+    pub e2: Vector,
+}
+
+impl ShapeProperties for SmoothTriangle {
+    fn transform(&self) -> Transform {
+        self.transform.clone()
+    }
+
+    fn set_transform(&mut self, t: Transform) {
+        self.transform = t;
+    }
+
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
+    // without a hit the barycentric coordinates are recovered from the point
+    fn normal_at(&self, p: Point) -> Vector {
+        let local_point = self.world_to_object(p);
+        let (u, v) = self.barycentric(local_point);
+        self.normal_to_world(self.interpolate_normal(u, v))
+    }
+
+    fn normal_at_hit(&self, _p: Point, hit: &Intersection) -> Vector {
+        self.normal_to_world(self.interpolate_normal(hit.u, hit.v))
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        (self.vector_to_world(self.e1), self.vector_to_world(self.e2))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let r_object = self.transform.inverse() * ray;
+        match triangle_intersection(&r_object, self.p1, self.e1, self.e2) {
+            Some((t, u, v)) => vec![Intersection::new_with_uv(t, self.as_trait(), u, v)],
+            None => vec![]
+        }
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let mut b = BoundingBox::empty();
+        b.add_point(self.p1);
+        b.add_point(self.p2);
+        b.add_point(self.p3);
+        b
+    }
+
+    fn as_trait(&self) -> &dyn ShapeProperties {
+        self
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+
+    fn set_material(&mut self, m: Material) {
+        self.material = m;
+    }
+}
+
+impl SmoothTriangle {
+    pub fn new(p1: Point, p2: Point, p3: Point, n1: Vector, n2: Vector, n3: Vector) -> Self {
+        SmoothTriangle {
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            material: Material::default(),
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1: p2 - p1,
+            e2: p3 - p1
+        }
+    }
+
+    fn interpolate_normal(&self, u: f64, v: f64) -> Vector {
+        self.n2 * u + self.n3 * v + self.n1 * (1.0 - u - v)
+    }
+
+    // u weights p2 and v weights p3, same convention as triangle_intersection
+    fn barycentric(&self, p: Point) -> (f64, f64) {
+        let p1_to_p = p - self.p1;
+        let d00 = self.e1.dot(self.e1);
+        let d01 = self.e1.dot(self.e2);
+        let d11 = self.e2.dot(self.e2);
+        let d20 = p1_to_p.dot(self.e1);
+        let d21 = p1_to_p.dot(self.e2);
+        let denom = d00 * d11 - d01 * d01;
+        let u = (d11 * d20 - d01 * d21) / denom;
+        let v = (d00 * d21 - d01 * d20) / denom;
+        (u, v)
+    }
+}
+
+#[cfg(test)]
+pub fn smooth_triangle(p1: Point, p2: Point, p3: Point, n1: Vector, n2: Vector, n3: Vector) -> SmoothTriangle {
+    SmoothTriangle::new(p1, p2, p3, n1, n2, n3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::float_eq;
+    use crate::intersection::prepare_computations;
+
+    fn default_smooth_triangle() -> SmoothTriangle {
+        smooth_triangle(
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(-1.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0)
+        )
+    }
+
+    #[test]
+    fn an_intersection_with_a_smooth_triangle_stores_u_v() {
+        let tri = default_smooth_triangle();
+        let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = tri.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert!(float_eq(xs[0].u, 0.45));
+        assert!(float_eq(xs[0].v, 0.25));
+    }
+
+    #[test]
+    fn a_smooth_triangle_uses_u_v_to_interpolate_the_normal() {
+        let tri = default_smooth_triangle();
+        let i = Intersection::new_with_uv(1.0, &tri, 0.45, 0.25);
+        let n = tri.normal_at_hit(Point::new(0.0, 0.0, 0.0), &i);
+        assert!(n == Vector::new(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn a_smooth_triangle_recovers_u_v_from_the_point() {
+        let tri = default_smooth_triangle();
+        let n = tri.normal_at(Point::new(-0.2, 0.3, 0.0));
+        assert!(n == Vector::new(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn preparing_the_normal_on_a_smooth_triangle() {
+        let tri = default_smooth_triangle();
+        let i = Intersection::new_with_uv(1.0, &tri, 0.45, 0.25);
+        let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = vec![i.clone()];
+        let comps = prepare_computations(&i, &r, &xs);
+        assert!(comps.normalv == Vector::new(-0.5547, 0.83205, 0.0));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::tuple::{Tuple, TupleProperties};
+use std::sync::OnceLock;
+
+// the visible range spectral rendering samples, in nanometres
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 780.0;
+
+// a uniformly distributed wavelength for r in [0, 1)
+pub fn sample_wavelength(r: f64) -> f64 {
+    MIN_WAVELENGTH + r * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+// piecewise gaussian used by the colour matching fit below
+fn g(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma_low } else { sigma_high };
+    f64::exp(-0.5 * t * t)
+}
+
+// the cie 1931 colour matching functions, after the multi-lobe fit of
+// wyman, sloan and shirley
+pub fn wavelength_to_xyz(nm: f64) -> (f64, f64, f64) {
+    let x = 1.056 * g(nm, 599.8, 37.9, 31.0) + 0.362 * g(nm, 442.0, 16.0, 26.7) - 0.065 * g(nm, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(nm, 568.8, 46.9, 40.5) + 0.286 * g(nm, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(nm, 437.0, 11.8, 36.0) + 0.681 * g(nm, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+// linear srgb of a single wavelength; the colours outside the srgb gamut are
+// clipped to it
+fn unbalanced_rgb(nm: f64) -> Tuple {
+    let (x, y, z) = wavelength_to_xyz(nm);
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    Tuple::new(f64::max(r, 0.0), f64::max(g, 0.0), f64::max(b, 0.0), 0.0)
+}
+
+// average of unbalanced_rgb over the sampled range
+fn white() -> Tuple {
+    static WHITE: OnceLock<Tuple> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = 1000;
+        let mut sum = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        for i in 0..steps {
+            sum = sum + unbalanced_rgb(sample_wavelength((i as f64 + 0.5) / steps as f64));
+        }
+        sum / steps as f64
+    })
+}
+
+// weight that turns what a ray of this wavelength saw into its share of an rgb
+// pixel; averaged over uniformly sampled wavelengths the weights are white, so
+// a scene without dispersion renders the same as with rgb rays
+pub fn wavelength_to_rgb(nm: f64) -> Tuple {
+    let w = white();
+    let c = unbalanced_rgb(nm);
+    Tuple::new(c.x / w.x, c.y / w.y, c.z / w.z, 0.0)
+}
+
+// the light a ray of the wavelength carries when it saw color
+pub fn spectral_sample(color: Tuple, nm: f64) -> Tuple {
+    color.hadamard_product(wavelength_to_rgb(nm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniformly_sampled_wavelengths_add_up_to_white() {
+        let steps = 350;
+        let mut sum = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        for i in 0..steps {
+            sum = sum + wavelength_to_rgb(sample_wavelength((i as f64 + 0.5) / steps as f64));
+        }
+        let mean = sum / steps as f64;
+        assert!(f64::abs(mean.x - 1.0) < 0.01 && f64::abs(mean.y - 1.0) < 0.01 && f64::abs(mean.z - 1.0) < 0.01);
+    }
+
+    #[test]
+    fn wavelengths_have_their_rainbow_colour() {
+        let blue = wavelength_to_rgb(450.0);
+        assert!(blue.z > blue.x && blue.z > blue.y);
+        let green = wavelength_to_rgb(530.0);
+        assert!(green.y > green.x && green.y > green.z);
+        let red = wavelength_to_rgb(640.0);
+        assert!(red.x > red.y && red.x > red.z);
+        // beyond the eye's range nothing is left
+        assert!(wavelength_to_rgb(779.0).x < 0.01);
+    }
+}
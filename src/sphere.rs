@@ -1,11 +1,12 @@
 use crate::*;
 use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
 use crate::intersection::Intersection;
-use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct Sphere {
     pub transform: Transform,
+    pub parent_transform: Transform,
     pub material: Material
 }
 
@@ -18,12 +19,23 @@ impl ShapeProperties for Sphere {
         self.transform = t;
     }
 
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
     fn normal_at(&self, p: Point) -> Vector {
-        let point_obj_space = self.transform.inverse() * p;
+        let point_obj_space = self.world_to_object(p);
         let normal_obj_space = point_obj_space - Point::new(0.0, 0.0, 0.0);
-        let mut world_normal = self.transform.inverse().transpose() * normal_obj_space;
-        world_normal.data.w = 0.0;
-        return world_normal.normalize();
+        self.normal_to_world(normal_obj_space)
+    }
+
+    fn tangents_at(&self, p: Point) -> (Vector, Vector) {
+        let (u, v) = shape_props::tangents_around_y(self.world_to_object(p));
+        (self.vector_to_world(u), self.vector_to_world(v))
     }
 
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
@@ -44,19 +56,17 @@ impl ShapeProperties for Sphere {
             let t1 = (-b - f64::sqrt(discriminant)) / (2.0 * a);
             let t2 = (-b + f64::sqrt(discriminant)) / (2.0 * a);
             result.push(
-                Intersection {
-                    t: t1,
-                    object: self.as_trait()
-                }
+                Intersection::new(t1, self.as_trait())
             );
-            result.push(Intersection {
-                t: t2,
-                object: self.as_trait()
-            });
+            result.push(Intersection::new(t2, self.as_trait()));
             result
         }
     }
 
+    fn local_bounds(&self) -> BoundingBox {
+        BoundingBox::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+
     fn as_trait(&self) -> &dyn ShapeProperties {
         self
     }
@@ -74,6 +84,7 @@ impl Sphere {
     pub fn default() -> Sphere {
         Sphere {
             transform: Transform::new(),
+            parent_transform: Transform::new(),
             material: Material::default()
         }
     }
@@ -186,4 +197,18 @@ mod tests {
 
         let xs = s.intersect(&r);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn the_tangent_of_a_sphere_runs_around_the_y_axis() {
+        let mut s = sphere();
+        let (u, v) = s.tangents_at(Point::new(0.0, 0.0, -1.0));
+        assert!(u == Vector::new(1.0, 0.0, 0.0));
+        assert!(v == Vector::new(0.0, 1.0, 0.0));
+
+        // stretched shapes keep their tangents perpendicular to the normal
+        s.set_transform(Transform::new().scaling(2.0, 1.0, 0.5));
+        let p = Point::new(1.2, 0.3, 0.25);
+        let (u, _) = s.tangents_at(p);
+        assert!(f64::abs(u.dot(s.normal_at(p))) < 0.00001);
+    }
+}
@@ -174,7 +174,7 @@ impl std::ops::Mul<Ray> for Transform {
     fn mul(self, rhs: Ray) -> Ray {
         let direction = Vector {data: self.dot(rhs.direction().data) };
         let origin = Point {data: self.dot(rhs.origin().data)};
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_wavelength(rhs.wavelength()).with_scattered(rhs.scattered())
     }
 }
 
@@ -186,7 +186,7 @@ impl std::ops::Mul<&Ray> for Transform {
     fn mul(self, rhs: &Ray) -> Ray {
         let direction = Vector {data: self.dot(rhs.direction().data) };
         let origin = Point {data: self.dot(rhs.origin().data)};
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).with_wavelength(rhs.wavelength()).with_scattered(rhs.scattered())
     }
 }
 
@@ -0,0 +1,180 @@
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::bounds::BoundingBox;
+use crate::intersection::{Intersection, EPSILON};
+
+#[derive(Clone)]
+pub struct Triangle {
+    pub material: Material,
+    pub transform: Transform,
+    pub parent_transform: Transform,
+    pub p1: Point,
+    pub p2: Point,
+    pub p3: Point,
+    // edges and face normal are precomputed once, they never change
+    pub e1: Vector,
+    pub e2: Vector,
+    pub normal: Vector,
+}
+
+// Moller-Trumbore, returns (t, u, v) with u/v the barycentric weights of p2/p3
+pub(crate) fn triangle_intersection(ray: &Ray, p1: Point, e1: Vector, e2: Vector) -> Option<(f64, f64, f64)> {
+    let dir_cross_e2 = ray.direction().cross(e2);
+    let det = e1.dot(dir_cross_e2);
+    if f64::abs(det) < EPSILON {
+        // ray is parallel to the triangle plane
+        return None;
+    }
+
+    let f = 1.0 / det;
+    let p1_to_origin = ray.origin() - p1;
+    let u = f * p1_to_origin.dot(dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * ray.direction().dot(origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = f * e2.dot(origin_cross_e1);
+    Some((t, u, v))
+}
+
+impl ShapeProperties for Triangle {
+    fn transform(&self) -> Transform {
+        self.transform.clone()
+    }
+
+    fn set_transform(&mut self, t: Transform) {
+        self.transform = t;
+    }
+
+    fn parent_transform(&self) -> Transform {
+        self.parent_transform.clone()
+    }
+
+    fn set_parent_transform(&mut self, t: Transform) {
+        self.parent_transform = t;
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        self.normal_to_world(self.normal)
+    }
+
+    fn tangents_at(&self, _p: Point) -> (Vector, Vector) {
+        (self.vector_to_world(self.e1), self.vector_to_world(self.e2))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let r_object = self.transform.inverse() * ray;
+        match triangle_intersection(&r_object, self.p1, self.e1, self.e2) {
+            Some((t, u, v)) => vec![Intersection::new_with_uv(t, self.as_trait(), u, v)],
+            None => vec![]
+        }
+    }
+
+    fn local_bounds(&self) -> BoundingBox {
+        let mut b = BoundingBox::empty();
+        b.add_point(self.p1);
+        b.add_point(self.p2);
+        b.add_point(self.p3);
+        b
+    }
+
+    fn as_trait(&self) -> &dyn ShapeProperties {
+        self
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+
+    fn set_material(&mut self, m: Material) {
+        self.material = m;
+    }
+}
+
+impl Triangle {
+    pub fn new(p1: Point, p2: Point, p3: Point) -> Self {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross(e1).normalize();
+        Triangle {
+            transform: Transform::new(),
+            parent_transform: Transform::new(),
+            material: Material::default(),
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn triangle(p1: Point, p2: Point, p3: Point) -> Triangle {
+    Triangle::new(p1, p2, p3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::float_eq;
+
+    fn default_triangle() -> Triangle {
+        triangle(Point::new(0.0, 1.0, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn constructing_a_triangle() {
+        let t = default_triangle();
+        assert!(t.e1 == Vector::new(-1.0, -1.0, 0.0));
+        assert!(t.e2 == Vector::new(1.0, -1.0, 0.0));
+        assert!(t.normal == Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn finding_the_normal_on_a_triangle() {
+        let t = default_triangle();
+        let n1 = t.normal_at(Point::new(0.0, 0.5, 0.0));
+        let n2 = t.normal_at(Point::new(-0.5, 0.75, 0.0));
+        let n3 = t.normal_at(Point::new(0.5, 0.25, 0.0));
+        assert!(n1 == t.normal);
+        assert!(n2 == t.normal);
+        assert!(n3 == t.normal);
+    }
+
+    #[test]
+    fn intersecting_a_ray_parallel_to_the_triangle() {
+        let t = default_triangle();
+        let r = Ray::new(Point::new(0.0, -1.0, -2.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(t.intersect(&r).len(), 0);
+    }
+
+    #[test]
+    fn a_ray_misses_the_triangle_edges() {
+        let t = default_triangle();
+        let r = Ray::new(Point::new(1.0, 1.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(t.intersect(&r).len(), 0);
+
+        let r = Ray::new(Point::new(-1.0, 1.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(t.intersect(&r).len(), 0);
+
+        let r = Ray::new(Point::new(0.0, -1.0, -2.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(t.intersect(&r).len(), 0);
+    }
+
+    #[test]
+    fn a_ray_strikes_a_triangle() {
+        let t = default_triangle();
+        let r = Ray::new(Point::new(0.0, 0.5, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = t.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert!(float_eq(xs[0].t, 2.0));
+    }
+}
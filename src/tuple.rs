@@ -283,6 +283,14 @@ impl ops::Sub<Vector> for Vector {
     }
 }
 
+impl ops::Add<Vector> for Vector {
+    type Output = Self;
+
+    fn add(self, rhs: Vector) -> Self {
+        Vector {data: self.data + rhs.data}
+    }
+}
+
 impl ops::Add<Vector> for Point {
     type Output = Point;
 
@@ -1,10 +1,378 @@
-// use crate::*;
-// use wavefront_obj::obj::ObjSet;
-//
-// fn parse() {
-//     let s = "a.obj";
-//     let a = wavefront_obj::obj::parse(s);
-//     if a.is_ok() {
-//         let b: ObjSet = a.unwrap();
-//     }
-// }
\ No newline at end of file
+use crate::*;
+use crate::shape_props::ShapeProperties;
+use crate::group::Group;
+use crate::triangle::Triangle;
+use crate::smooth_triangle::SmoothTriangle;
+use crate::mesh::{Mesh, Face};
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    // 1-based line in the source, 0 when the file could not be read at all
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub struct ObjParser {
+    pub vertices: Vec<Point>,
+    pub normals: Vec<Vector>,
+    // only the mesh path uses these, triangle shapes have no texture coordinates
+    pub texture_coords: Vec<(f64, f64)>,
+    // statements we don't support (mtllib, usemap, s, l, ...), comments are not counted
+    pub ignored_lines: usize,
+    pub default_group: Group,
+    // named `g` / `o` groups in the order they first appear
+    pub groups: Vec<(String, Group)>,
+    current_group: Option<usize>,
+    // when loading a mesh the triangles only go to the index buffer, groups stay empty
+    mesh_only: bool,
+    pub faces: Vec<Face>,
+}
+
+// one corner of a face: vertex index plus optional texture and normal index
+struct FaceVertex {
+    vertex: usize,
+    texture: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl ObjParser {
+    fn new(mesh_only: bool) -> Self {
+        ObjParser {
+            vertices: vec![],
+            normals: vec![],
+            texture_coords: vec![],
+            ignored_lines: 0,
+            default_group: Group::new(),
+            groups: vec![],
+            current_group: None,
+            mesh_only,
+            faces: vec![],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|(n, _)| n == name).map(|(_, g)| g)
+    }
+
+    // a single group holding the default group's triangles plus every named group,
+    // `vt` coordinates are dropped here, into_mesh keeps them
+    pub fn into_group(self) -> Group {
+        let mut result = self.default_group;
+        for (_, g) in self.groups {
+            result.add_child(Box::new(g));
+        }
+        result
+    }
+
+    // every face of every group in one mesh sharing the parsed buffers
+    pub fn into_mesh(self) -> Mesh {
+        Mesh::new(self.vertices, self.normals, self.texture_coords, self.faces)
+    }
+
+    fn parse_line(&mut self, line: &str, line_number: usize) -> Result<(), ParseError> {
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let xyz = parse_floats(&args, 3, line_number)?;
+                self.vertices.push(Point::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "vn" => {
+                let xyz = parse_floats(&args, 3, line_number)?;
+                self.normals.push(Vector::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "vt" => {
+                let uv = parse_floats(&args, 2, line_number)?;
+                self.texture_coords.push((uv[0], uv[1]));
+            }
+            "f" => self.parse_face(&args, line_number)?,
+            "g" | "o" => {
+                let name = args.join(" ");
+                self.current_group = match self.groups.iter().position(|(n, _)| *n == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        self.groups.push((name, Group::new()));
+                        Some(self.groups.len() - 1)
+                    }
+                };
+            }
+            _ => self.ignored_lines += 1,
+        }
+
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str], line_number: usize) -> Result<(), ParseError> {
+        if args.len() < 3 {
+            return Err(ParseError {
+                line: line_number,
+                message: format!("a face needs at least 3 vertices, found {}", args.len()),
+            });
+        }
+
+        let mut corners: Vec<FaceVertex> = vec![];
+        for arg in args {
+            corners.push(self.parse_face_vertex(arg, line_number)?);
+        }
+
+        // fan triangulation around the first corner, fine for convex polygons
+        for i in 1..corners.len() - 1 {
+            let (a, b, c) = (&corners[0], &corners[i], &corners[i + 1]);
+            if self.mesh_only {
+                let index = |corner: &FaceVertex| corner.vertex as u32;
+                let normals = match (a.normal, b.normal, c.normal) {
+                    (Some(n1), Some(n2), Some(n3)) => Some([n1 as u32, n2 as u32, n3 as u32]),
+                    _ => None,
+                };
+                let uvs = match (a.texture, b.texture, c.texture) {
+                    (Some(t1), Some(t2), Some(t3)) => Some([t1 as u32, t2 as u32, t3 as u32]),
+                    _ => None,
+                };
+                self.faces.push(Face { vertices: [index(a), index(b), index(c)], normals, uvs });
+                continue;
+            }
+
+            let p1 = self.vertices[a.vertex];
+            let p2 = self.vertices[b.vertex];
+            let p3 = self.vertices[c.vertex];
+            let shape: Box<dyn ShapeProperties> = match (a.normal, b.normal, c.normal) {
+                (Some(n1), Some(n2), Some(n3)) => Box::new(SmoothTriangle::new(
+                    p1, p2, p3, self.normals[n1], self.normals[n2], self.normals[n3])),
+                _ => Box::new(Triangle::new(p1, p2, p3)),
+            };
+
+            match self.current_group {
+                Some(index) => self.groups[index].1.add_child(shape),
+                None => self.default_group.add_child(shape),
+            }
+        }
+
+        Ok(())
+    }
+
+    // accepts `v`, `v/vt`, `v//vn` and `v/vt/vn`
+    fn parse_face_vertex(&self, arg: &str, line_number: usize) -> Result<FaceVertex, ParseError> {
+        let parts: Vec<&str> = arg.split('/').collect();
+        let vertex = resolve_index(parts[0], self.vertices.len(), "vertex", line_number)?;
+        let texture = match parts.get(1) {
+            Some(p) if !p.is_empty() => Some(resolve_index(p, self.texture_coords.len(), "texture coordinate", line_number)?),
+            _ => None,
+        };
+        let normal = match parts.get(2) {
+            Some(p) if !p.is_empty() => Some(resolve_index(p, self.normals.len(), "normal", line_number)?),
+            _ => None,
+        };
+
+        Ok(FaceVertex { vertex, texture, normal })
+    }
+}
+
+fn parse_floats(args: &[&str], count: usize, line_number: usize) -> Result<Vec<f64>, ParseError> {
+    if args.len() < count {
+        return Err(ParseError {
+            line: line_number,
+            message: format!("expected {} values, found {}", count, args.len()),
+        });
+    }
+
+    args[..count].iter().map(|a| {
+        a.parse::<f64>().map_err(|_| ParseError {
+            line: line_number,
+            message: format!("invalid number '{}'", a),
+        })
+    }).collect()
+}
+
+// obj indices are 1-based, negative ones count back from the latest element
+fn resolve_index(token: &str, len: usize, what: &str, line_number: usize) -> Result<usize, ParseError> {
+    let index = token.parse::<i64>().map_err(|_| ParseError {
+        line: line_number,
+        message: format!("invalid {} index '{}'", what, token),
+    })?;
+
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(ParseError {
+            line: line_number,
+            message: format!("{} index {} out of range, {} defined so far", what, index, len),
+        });
+    }
+
+    Ok(resolved as usize)
+}
+
+pub fn parse_obj(content: &str) -> Result<ObjParser, ParseError> {
+    parse(content, ObjParser::new(false))
+}
+
+// the whole file as one mesh instead of a group of triangle shapes
+pub fn parse_obj_mesh(content: &str) -> Result<Mesh, ParseError> {
+    Ok(parse(content, ObjParser::new(true))?.into_mesh())
+}
+
+fn parse(content: &str, mut parser: ObjParser) -> Result<ObjParser, ParseError> {
+    for (i, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        parser.parse_line(line, i + 1)?;
+    }
+
+    Ok(parser)
+}
+
+pub fn parse_obj_file(path: &str) -> Result<ObjParser, ParseError> {
+    parse_obj(&read_file(path)?)
+}
+
+pub fn parse_obj_mesh_file(path: &str) -> Result<Mesh, ParseError> {
+    parse_obj_mesh(&read_file(path)?)
+}
+
+fn read_file(path: &str) -> Result<String, ParseError> {
+    fs::read_to_string(path).map_err(|e| ParseError {
+        line: 0,
+        message: format!("cannot read {}: {}", path, e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::float_eq;
+
+    #[test]
+    fn ignoring_unrecognized_lines() {
+        let gibberish = "There was a young lady named Bright\n\
+                         who traveled much faster than light.\n\
+                         She set out one day\n\
+                         in a relative way,\n\
+                         and came back the previous night.\n";
+        let parser = parse_obj(gibberish).unwrap();
+        assert_eq!(parser.ignored_lines, 5);
+    }
+
+    #[test]
+    fn vertex_records() {
+        let file = "v -1 1 0\nv -1.0000 0.5000 0.0000\nv 1 0 0\nv 1 1 0\n";
+        let parser = parse_obj(file).unwrap();
+        assert_eq!(parser.vertices.len(), 4);
+        assert!(parser.vertices[0] == Point::new(-1.0, 1.0, 0.0));
+        assert!(parser.vertices[1] == Point::new(-1.0, 0.5, 0.0));
+        assert!(parser.vertices[2] == Point::new(1.0, 0.0, 0.0));
+        assert!(parser.vertices[3] == Point::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn parsing_triangle_faces() {
+        let file = "# a square\nv -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 3\nf 1 3 4\n";
+        let parser = parse_obj(file).unwrap();
+        assert_eq!(parser.default_group.len(), 2);
+        // comments are not unsupported statements
+        assert_eq!(parser.ignored_lines, 0);
+    }
+
+    #[test]
+    fn triangulating_polygons() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nv 0 2 0\n\nf 1 2 3 4 5\n";
+        let parser = parse_obj(file).unwrap();
+        assert_eq!(parser.default_group.len(), 3);
+
+        let r = Ray::new(Point::new(0.0, 1.5, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = parser.default_group.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert!(float_eq(xs[0].t, 2.0));
+    }
+
+    #[test]
+    fn triangles_in_groups() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\
+                    g FirstGroup\nf 1 2 3\ng SecondGroup\nf 1 3 4\n";
+        let parser = parse_obj(file).unwrap();
+        assert!(parser.default_group.is_empty());
+        assert_eq!(parser.group("FirstGroup").unwrap().len(), 1);
+        assert_eq!(parser.group("SecondGroup").unwrap().len(), 1);
+
+        let g = parser.into_group();
+        assert_eq!(g.len(), 2);
+    }
+
+    #[test]
+    fn vertex_normal_records() {
+        let file = "vn 0 0 1\nvn 0.707 0 -0.707\nvn 1 2 3\n";
+        let parser = parse_obj(file).unwrap();
+        assert!(parser.normals[0] == Vector::new(0.0, 0.0, 1.0));
+        assert!(parser.normals[1] == Vector::new(0.707, 0.0, -0.707));
+        assert!(parser.normals[2] == Vector::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn faces_with_normals() {
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\n\
+                    vn -1 0 0\nvn 1 0 0\nvn 0 1 0\nvt 0.5 0.5\n\
+                    f 1//3 2//1 3//2\nf 1/1/3 2/1/1 3/1/2\n";
+        let parser = parse_obj(file).unwrap();
+        assert_eq!(parser.default_group.len(), 2);
+        assert_eq!(parser.texture_coords.len(), 1);
+
+        let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = parser.default_group.intersect(&r);
+        let n = xs[0].object.normal_at_hit(r.position_at(xs[0].t), &xs[0]);
+        assert!(n == Vector::new(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn loading_a_file_as_a_mesh() {
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\nv 0 -1 0\nv 0 2 0\n\
+                    vn -1 0 0\nvn 1 0 0\nvn 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n\
+                    f 1/3/3 2/1/1 3/2/2\ng Other\nf 2 4 3 1 5\n";
+        let mesh = parse_obj_mesh(file).unwrap();
+        // the pentagon is fanned into three triangles, groups are flattened
+        assert_eq!(mesh.len(), 4);
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.faces[0], Face { vertices: [0, 1, 2], normals: Some([2, 0, 1]), uvs: Some([2, 0, 1]) });
+        assert_eq!(mesh.faces[3], Face { vertices: [1, 0, 4], normals: None, uvs: None });
+
+        // the same smooth normal as the triangle shapes give
+        let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = mesh.intersect(&r);
+        let n = mesh.normal_at_hit(r.position_at(xs[0].t), &xs[0]);
+        assert!(n == Vector::new(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn negative_indices_are_relative() {
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\nf -3 -2 -1\n";
+        let parser = parse_obj(file).unwrap();
+        assert_eq!(parser.default_group.len(), 1);
+    }
+
+    #[test]
+    fn errors_report_the_line_number() {
+        let file = "v 0 1 0\nv -1 0 0\n# comment\nv 1 zero 0\n";
+        let err = parse_obj(file).err().unwrap();
+        assert_eq!(err.line, 4);
+
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\nf 1 2 4\n";
+        let err = parse_obj(file).err().unwrap();
+        assert_eq!(err.line, 4);
+    }
+}
@@ -1,18 +1,24 @@
-use crate::light::{PointLight, PointLightProperties};
-use crate::tuple::{Tuple, TupleProperties, Point, PointProperties, VectorProperties};
+use crate::light::{Light, LightSample, PointLight, PointLightProperties};
+use crate::tuple::{Tuple, TupleProperties, Point, PointProperties, Vector, VectorProperties};
 use crate::ray::Ray;
 use std::cmp::Ordering::Equal;
 use crate::transformation::{Transform, TransformProperty};
 use crate::intersection::{Intersection, prepare_computations, schlick};
 use crate::intersection::AugIntersection;
-use crate::material::{phong_lighting, Material, MaterialProperties};
+use crate::material::{lighting, base_reflectance, fresnel_schlick, Material, MaterialProperties, Shading};
 use crate::shape_props::ShapeProperties;
 use crate::sphere::Sphere;
 use crate::shape_props;
+use crate::bvh::Bvh;
+use rand::Rng;
 
 pub struct World {
-    pub objects: Vec<Box<ShapeProperties>>,
-    pub light: PointLight
+    // private so every change goes through a method that drops the bvh
+    objects: Vec<Box<dyn ShapeProperties>>,
+    // every light contributes its own phong term and shadow test
+    pub lights: Vec<Box<dyn Light>>,
+    // optional acceleration structure over objects, see build_bvh
+    bvh: Option<Bvh>
 }
 
 pub trait WorldProperties {
@@ -22,19 +28,38 @@ pub trait WorldProperties {
 
     fn color_at_ray(& self, r: &Ray, remaining: i32) -> Tuple;
 
-    fn is_shadow(& self, p: Point) -> bool;
+    // whether something blocks the way from p towards the light sample
+    fn is_shadow(& self, p: Point, sample: &LightSample) -> bool;
+
+    // the light's samples for p with their shadow rays traced, drawn once so that
+    // lighting and the shadow test see the same jittered points on an area light
+    fn light_samples(&self, p: Point, light: &dyn Light) -> Vec<LightSample>;
 
     fn reflected_color(& self, comps: &AugIntersection, remaining: i32) -> Tuple;
 
     fn refracted_color(& self, comps: &AugIntersection, remaining: i32) -> Tuple;
 
+    // average colour seen along the rays lobe_directions spreads around center,
+    // leaving origin for the hit in comps
+    fn lobe_color(&self, comps: &AugIntersection, origin: Point, center: Vector, normal: Vector, material: &Material, remaining: i32) -> Tuple;
+
+    // reflection of a pbr material, blended with the already traced refraction
+    fn pbr_reflected_color(&self, comps: &AugIntersection, material: &Material, remaining: i32, refracted: Tuple) -> Tuple;
+
     fn intersect(& self, ray_world: &Ray) -> Vec<Intersection>;
 
     fn shade_hit(&self, comps: &AugIntersection, remaining: i32) -> Tuple;
 
-    fn objects(&self) -> Vec<Box<dyn ShapeProperties>>;
+    fn objects(&self) -> &[Box<dyn ShapeProperties>];
+
+    // the mutators below drop the bvh, call build_bvh again once the objects are in place
+    fn objects_mut(&mut self) -> &mut Vec<Box<dyn ShapeProperties>>;
+
+    fn add_object(&mut self, object: Box<dyn ShapeProperties>);
 
     fn set_objects(&mut self, vec: Vec<Box<dyn ShapeProperties>>);
+
+    fn build_bvh(&mut self);
 }
 
 impl WorldProperties for World {
@@ -53,12 +78,12 @@ impl WorldProperties for World {
             Transform::new().scaling(0.5, 0.5, 0.5)
         );
 
-        let objs: Vec<Box<ShapeProperties>>= vec![
+        let objs: Vec<Box<dyn ShapeProperties>>= vec![
             Box::new(s1),
             Box::new(s2)
         ];
 
-        World {objects: objs, light}
+        World {objects: objs, lights: vec![Box::new(light)], bvh: None}
     }
 
     fn default() -> World {
@@ -77,22 +102,16 @@ impl WorldProperties for World {
             Transform::new().scaling(0.5, 0.5, 0.5)
         );
 
-        let objs: Vec<Box<ShapeProperties>> = vec![
+        let objs: Vec<Box<dyn ShapeProperties>> = vec![
             Box::new(s1),
             Box::new(s2)
         ];
 
-        World {objects: objs, light}
+        World {objects: objs, lights: vec![Box::new(light)], bvh: None}
     }
 
     fn color_at_ray(&self, r: &Ray, remaining: i32) -> Tuple {
-        let objs = &self.objects;
-        let mut result: Vec<Intersection> = vec![];
-        for mut s in objs.iter() {
-            let mut intersect_values = s.intersect(&r);
-            result.append(&mut intersect_values);
-        }
-        result.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Equal));
+        let result = self.intersect(r);
 
         let intersection = shape_props::hit(result.clone());
         return if intersection == None {
@@ -100,21 +119,19 @@ impl WorldProperties for World {
         } else {
             let inter = intersection.unwrap();
             let aug_inter = prepare_computations(&inter, &r, &result);
-            shade_hit(self, &aug_inter, remaining)
+            let color = shade_hit(self, &aug_inter, remaining);
+            // light reaching the eye through the inside of an object is absorbed on the way
+            match aug_inter.medium {
+                Some(medium) => color.hadamard_product(medium.material().transmittance(inter.t * r.direction().mag())),
+                None => color
+            }
         }
     }
 
-    fn is_shadow(&self, point: Point) -> bool {
-        let shadow_ray_dir = self.light.position() - point;
-        let distance = shadow_ray_dir.mag();
-        let r = Ray::new(point, shadow_ray_dir.normalize());
-        let objs = &self.objects;
-        let mut result: Vec<Intersection> = vec![];
-        for mut s in objs.iter() {
-            let mut vals = s.intersect(&r);
-            result.append(&mut vals);
-        }
-        result.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Equal));
+    fn is_shadow(&self, point: Point, sample: &LightSample) -> bool {
+        let distance = sample.distance;
+        let r = Ray::new(point, sample.direction);
+        let result = self.intersect(&r);
         let intersection = shape_props::hit(result);
 
         if intersection != None && intersection.unwrap().t < distance {
@@ -124,6 +141,14 @@ impl WorldProperties for World {
         return false;
     }
 
+    fn light_samples(&self, point: Point, light: &dyn Light) -> Vec<LightSample> {
+        let mut samples = light.samples(point);
+        for sample in &mut samples {
+            sample.visible = !self.is_shadow(point, sample);
+        }
+        samples
+    }
+
     fn reflected_color(& self, aug_intersection: &AugIntersection, remaining: i32) -> Tuple {
         if remaining <= 0 {
             return Tuple::new(0.0, 0.0, 0.0, 0.0);
@@ -134,11 +159,41 @@ impl WorldProperties for World {
             return Tuple::new(0.0, 0.0, 0.0, 0.0);
         }
 
-        let reflect_ray = Ray::new(aug_intersection.over_point, aug_intersection.reflectv);
-        let color = self.color_at_ray(&reflect_ray, remaining - 1);
+        let material = aug_intersection.object.material();
+        let color = self.lobe_color(aug_intersection, aug_intersection.over_point, aug_intersection.reflectv, aug_intersection.normalv, &material, remaining);
         color * reflective_factor
     }
 
+    fn lobe_color(&self, comps: &AugIntersection, origin: Point, center: Vector, normal: Vector, material: &Material, remaining: i32) -> Tuple {
+        let directions = lobe_directions(center, normal, material, comps.scattered);
+        let scattered = comps.scattered || material.roughness > 0.0;
+        let mut sum = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        for direction in &directions {
+            let ray = Ray::new(origin, *direction).with_wavelength(comps.wavelength).with_scattered(scattered);
+            sum = sum + self.color_at_ray(&ray, remaining - 1);
+        }
+        sum / directions.len() as f64
+    }
+
+    // pbr surfaces reflect as much as fresnel says instead of `reflective`: tinted
+    // for metals, mostly at grazing angles for dielectrics
+    fn pbr_reflected_color(&self, comps: &AugIntersection, material: &Material, remaining: i32, refracted: Tuple) -> Tuple {
+        let fresnel = if material.transparency > 0.0 {
+            // schlick also covers total internal reflection
+            let r = schlick(comps);
+            Tuple::new(r, r, r, 0.0)
+        } else {
+            let base_color = material.color_at_object(comps.object, comps.over_point);
+            fresnel_schlick(base_reflectance(material, base_color), comps.eyev.dot(comps.shading_normalv))
+        };
+        let transmitted = 1.0 - (fresnel.x + fresnel.y + fresnel.z) / 3.0;
+        if remaining <= 0 {
+            return refracted * transmitted;
+        }
+        let reflected = self.lobe_color(comps, comps.over_point, comps.reflectv, comps.normalv, material, remaining);
+        reflected.hadamard_product(fresnel) + refracted * transmitted
+    }
+
     fn refracted_color(& self, comps: &AugIntersection, remaining: i32) -> Tuple {
         if remaining == 0 || comps.object.material().transparency <= 0.000001 {
             return Tuple::new(0.0, 0.0, 0.0, 0.0);
@@ -152,36 +207,46 @@ impl WorldProperties for World {
 
         let cos_t = f64::sqrt(1.0 - sin2_t);
         let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-        let refracted_ray = Ray::new(comps.under_point, direction);
-        let color = self.color_at_ray(&refracted_ray, remaining - 1) * comps.object.material().transparency;
+        let material = comps.object.material();
+        let color = self.lobe_color(comps, comps.under_point, direction, -1.0 * comps.normalv, &material, remaining) * material.transparency;
 
         return color;
     }
 
     fn intersect(&self, ray_world: &Ray) -> Vec<Intersection<'_>> {
-        let mut v: Vec<Intersection> = vec![];
-        for obj in &self.objects {
-            let mut xs = obj.intersect(&ray_world);
-            v.append(&mut xs);
-        }
+        let mut v: Vec<Intersection> = match &self.bvh {
+            Some(bvh) => bvh.intersect(&self.objects, ray_world),
+            _ => {
+                let mut xs: Vec<Intersection> = vec![];
+                for obj in &self.objects {
+                    xs.append(&mut obj.intersect(ray_world));
+                }
+                xs
+            }
+        };
         v.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Equal));
         return v;
     }
 
     fn shade_hit(&self, comps: &AugIntersection, remaining: i32) -> Tuple {
-        let is_shadow: bool = self.is_shadow(comps.over_point);
-        let surface = phong_lighting(
-            &comps.object.material(),
-            self.light,
-            comps.over_point,
-            comps.eyev,
-            comps.normalv,
-            is_shadow,
-            comps.object
-        );
-        let reflected = self.reflected_color(&comps, remaining);
-        let refracted = self.refracted_color(&comps, remaining);
         let material = comps.object.material();
+        let mut surface = Tuple::new(0.0, 0.0, 0.0, 0.0);
+        for light in &self.lights {
+            surface = surface + lighting(
+                &material,
+                light.as_ref(),
+                comps.over_point,
+                comps.eyev,
+                comps.shading_normalv,
+                &self.light_samples(comps.over_point, light.as_ref()),
+                comps.object
+            );
+        }
+        let refracted = self.refracted_color(&comps, remaining);
+        if material.shading == Shading::Pbr {
+            return surface + self.pbr_reflected_color(comps, &material, remaining, refracted);
+        }
+        let reflected = self.reflected_color(comps, remaining);
 
         if material.reflective > 0.0 && material.transparency > 0.0 {
             let reflectance = schlick(&comps);
@@ -191,43 +256,440 @@ impl WorldProperties for World {
         }
     }
 
-    fn objects(&self) -> Vec<Box<dyn ShapeProperties>> {
-        unimplemented!()
+    fn objects(&self) -> &[Box<dyn ShapeProperties>] {
+        &self.objects
+    }
+
+    fn objects_mut(&mut self) -> &mut Vec<Box<dyn ShapeProperties>> {
+        self.bvh = None;
+        &mut self.objects
+    }
+
+    fn add_object(&mut self, object: Box<dyn ShapeProperties>) {
+        self.objects.push(object);
+        self.bvh = None;
     }
 
     fn set_objects(&mut self, vec: Vec<Box<dyn ShapeProperties>>) {
         self.objects = vec;
+        self.bvh = None;
+    }
+
+    fn build_bvh(&mut self) {
+        for obj in &mut self.objects {
+            obj.build_bvh();
+        }
+        self.bvh = Some(Bvh::build(&self.objects));
     }
 }
 
 pub fn shade_hit(w: & World, comps: &AugIntersection, remaining: i32) -> Tuple {
-    let is_shadow: bool = w.is_shadow(comps.over_point);
-    let surface = phong_lighting(
-        &comps.object.material(),
-        w.light,
-        comps.over_point,
-        comps.eyev,
-        comps.normalv,
-        is_shadow,
-        comps.object
-    );
-
-    let reflected = w.reflected_color(&comps, remaining);
-    let refracted = w.refracted_color(&comps, remaining);
+    w.shade_hit(comps, remaining)
+}
 
-    let material = comps.object.material();
+// center nudged towards a point on the unit sphere picked by r1 and r2 in [0, 1),
+// by as much as roughness; a direction that ends up behind the surface is
+// mirrored back in front of it
+pub fn glossy_direction(center: Vector, normal: Vector, roughness: f64, r1: f64, r2: f64) -> Vector {
+    let z = 1.0 - 2.0 * r1;
+    let r = f64::sqrt(f64::max(1.0 - z * z, 0.0));
+    let phi = 2.0 * std::f64::consts::PI * r2;
+    let offset = Vector::new(r * phi.cos(), r * phi.sin(), z);
+    let direction = (center.normalize() + offset * roughness).normalize();
+    let side = direction.dot(normal);
+    match side < 0.0 {
+        true => direction - normal * (2.0 * side),
+        false => direction
+    }
+}
 
-    if material.reflective > 0.0 && material.transparency > 0.0 {
-        let reflectance = schlick(&comps);
-        return surface + reflected * reflectance + refracted * (1.0 - reflectance);
-    } else {
-        surface + reflected + refracted
+// the directions a hit traces around center: one exact ray when smooth, otherwise
+// gloss_samples rays, one per stratum, or a single ray for a ray that already came
+// off a rough surface so the cost does not multiply with every bounce
+pub fn lobe_directions(center: Vector, normal: Vector, material: &Material, scattered: bool) -> Vec<Vector> {
+    if material.roughness <= 0.0 {
+        return vec![center];
     }
+    let count = match scattered {
+        true => 1,
+        false => usize::max(material.gloss_samples, 1)
+    };
+    let mut rng = rand::thread_rng();
+    (0..count).map(|i| {
+        let (r1, r2) = stratum(i, count, rng.gen(), rng.gen());
+        glossy_direction(center, normal, material.roughness, r1, r2)
+    }).collect()
+}
 
+// the i-th of count equal area cells covering the unit square, with u, v placed inside it;
+// rows of ceil(sqrt(count)) cells, the last row shorter and its cells wider
+pub fn stratum(i: usize, count: usize, u: f64, v: f64) -> (f64, f64) {
+    let columns = f64::ceil(f64::sqrt(count as f64)) as usize;
+    let row_start = i / columns * columns;
+    let in_row = usize::min(columns, count - row_start);
+    let x = ((i - row_start) as f64 + u) / in_row as f64;
+    let y = (row_start as f64 + v * in_row as f64) / count as f64;
+    (x, y)
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::material::float_eq;
+    use crate::tuple::Vector;
+    use crate::light::{AreaLight, DirectionalLight};
+    use crate::material::{phong_lighting, pbr_lighting};
+    use crate::plane::plane;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    // metallic and roughness in [0, 1], ambient keeps the phong default
+    fn pbr(color: Tuple, metallic: f64, roughness: f64, refractive_index: f64) -> Material {
+        let mut m = Material::new(color, 0.1, 0.9, 0.9, 200.0);
+        m.shading = Shading::Pbr;
+        m.metallic = metallic;
+        m.roughness = roughness;
+        m.refractive_index = refractive_index;
+        m
+    }
+
+    // fraction of the light's sample points visible from p, 0.0 to 1.0
+    fn intensity_at(w: &World, p: Point, light: &dyn Light) -> f64 {
+        let samples = w.light_samples(p, light);
+        let visible = samples.iter().filter(|sample| sample.visible).count();
+        visible as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn intersecting_through_the_bvh_matches_a_linear_scan() {
+        let mut w = World::new();
+        for i in 0..10 {
+            let mut s = Sphere::default();
+            s.set_transform(Transform::new().translate(3.0 * i as f64, 0.0, 0.0));
+            w.add_object(Box::new(s));
+        }
+        let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let linear: Vec<f64> = w.intersect(&r).iter().map(|i| i.t).collect();
+
+        w.build_bvh();
+        let accelerated: Vec<f64> = w.intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(linear.len(), 24);
+        assert_eq!(linear, accelerated);
+    }
+
+    #[test]
+    fn moving_an_object_after_building_the_bvh_is_not_missed() {
+        let mut w = World::new();
+        w.objects_mut().clear();
+        for i in 0..10 {
+            let mut s = Sphere::default();
+            s.set_transform(Transform::new().translate(3.0 * i as f64, 0.0, 0.0));
+            w.add_object(Box::new(s));
+        }
+        w.build_bvh();
+
+        // same number of objects, only the bounds change
+        w.objects_mut()[9].set_transform(Transform::new().translate(0.0, 10.0, 0.0));
+        let r = Ray::new(Point::new(0.0, 20.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let xs = w.intersect(&r);
+        assert_eq!(xs.len(), 4);
+        assert!(float_eq(xs[0].t, 9.0));
+
+        // and after a rebuild too
+        w.build_bvh();
+        assert_eq!(w.intersect(&r).len(), 4);
+    }
+
+    #[test]
+    fn shading_sums_the_contribution_of_every_light() {
+        let mut w = World::new();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let one_light = w.color_at_ray(&r, 1);
+
+        w.lights.push(Box::new(PointLight::new(Point::new(-10.0, 10.0, -10.0), Tuple::new(1.0, 1.0, 1.0, 1.0))));
+        let two_lights = w.color_at_ray(&r, 1);
+        assert!(two_lights == one_light * 2.0);
+
+        w.lights.clear();
+        assert!(w.color_at_ray(&r, 1) == Tuple::new(0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn shadows_are_tested_per_light() {
+        let w = World::new();
+        let p = Point::new(10.0, -10.0, 10.0);
+        let behind = PointLight::new(Point::new(-10.0, 10.0, -10.0), Tuple::new(1.0, 1.0, 1.0, 1.0));
+        let beside = PointLight::new(Point::new(20.0, -10.0, 10.0), Tuple::new(1.0, 1.0, 1.0, 1.0));
+        assert!(float_eq(intensity_at(&w, p, &behind), 0.0));
+        assert!(float_eq(intensity_at(&w, p, &beside), 1.0));
+    }
+
+    #[test]
+    fn a_directional_light_casts_shadows_from_any_distance() {
+        let w = World::new();
+        let sun = DirectionalLight::new(Vector::new(0.0, -1.0, 0.0), Tuple::new(1.0, 1.0, 1.0, 1.0));
+        assert!(float_eq(intensity_at(&w, Point::new(0.0, -1000.0, 0.0), &sun), 0.0));
+        assert!(float_eq(intensity_at(&w, Point::new(2.0, -1000.0, 0.0), &sun), 1.0));
+    }
+
+    #[test]
+    fn an_area_light_is_partly_visible_from_the_penumbra() {
+        let w = World::new();
+        let light = AreaLight::new(
+            Point::new(-0.5, -0.5, -5.0),
+            Vector::new(1.0, 0.0, 0.0), 2,
+            Vector::new(0.0, 1.0, 0.0), 2,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        assert!(float_eq(intensity_at(&w, Point::new(0.0, 0.0, 2.0), &light), 0.0));
+        assert!(float_eq(intensity_at(&w, Point::new(1.0, -1.0, 2.0), &light), 0.25));
+        assert!(float_eq(intensity_at(&w, Point::new(1.5, 0.0, 2.0), &light), 0.5));
+        assert!(float_eq(intensity_at(&w, Point::new(1.25, 1.25, 3.0), &light), 0.75));
+        assert!(float_eq(intensity_at(&w, Point::new(0.0, 0.0, -2.0), &light), 1.0));
+    }
+
+    #[test]
+    fn shadows_and_lighting_share_the_jittered_light_samples() {
+        let w = World::new();
+        let mut light = AreaLight::new(
+            Point::new(-0.5, -0.5, -5.0),
+            Vector::new(1.0, 0.0, 0.0), 8,
+            Vector::new(0.0, 1.0, 0.0), 8,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        light.jitter = true;
+        let shape = &w.objects()[0];
+        let m = Material::default();
+        let p = Point::new(1.5, 0.0, 2.0);
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+
+        let samples = w.light_samples(p, &light);
+        let visible = samples.iter().filter(|sample| sample.visible).count();
+        assert!(visible > 0 && visible < samples.len());
+        assert!(samples.iter().all(|sample| sample.visible != w.is_shadow(p, sample)));
+
+        // the lit and the shadowed samples add up to the whole light, so lighting
+        // used exactly the points that were tested for shadow
+        let hidden: Vec<LightSample> = samples.iter().map(|s| LightSample { visible: !s.visible, ..*s }).collect();
+        let all: Vec<LightSample> = samples.iter().map(|s| LightSample { visible: true, ..*s }).collect();
+        let lit = phong_lighting(&m, &light, p, eyev, normalv, &samples, shape.as_trait());
+        let shadowed = phong_lighting(&m, &light, p, eyev, normalv, &hidden, shape.as_trait());
+        let ambient = phong_lighting(&m, &light, p, eyev, normalv, &[], shape.as_trait());
+        assert!(lit + shadowed - ambient == phong_lighting(&m, &light, p, eyev, normalv, &all, shape.as_trait()));
+    }
+
+    #[test]
+    fn lighting_samples_the_area_light() {
+        let w = World::new();
+        let light = AreaLight::new(
+            Point::new(-0.5, -0.5, -5.0),
+            Vector::new(1.0, 0.0, 0.0), 2,
+            Vector::new(0.0, 1.0, 0.0), 2,
+            Tuple::new(1.0, 1.0, 1.0, 1.0)
+        );
+        let shape = &w.objects()[0];
+        let m = Material::new(Tuple::new(1.0, 1.0, 1.0, 1.0), 0.1, 0.9, 0.0, 200.0);
+        let eye = Point::new(0.0, 0.0, -5.0);
+
+        let pt = Point::new(0.0, 0.0, -1.0);
+        let eyev = (eye - pt).normalize();
+        let normalv = Vector::new(pt.x(), pt.y(), pt.z());
+        let c = phong_lighting(&m, &light, pt, eyev, normalv, &light.samples(pt), shape.as_trait());
+        assert!(float_eq(c.x, 0.9965) && float_eq(c.y, 0.9965) && float_eq(c.z, 0.9965));
+
+        let pt = Point::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        let eyev = (eye - pt).normalize();
+        let normalv = Vector::new(pt.x(), pt.y(), pt.z());
+        let c = phong_lighting(&m, &light, pt, eyev, normalv, &light.samples(pt), shape.as_trait());
+        assert!(float_eq(c.x, 0.62318) && float_eq(c.y, 0.62318) && float_eq(c.z, 0.62318));
+    }
+
+    #[test]
+    fn a_rough_white_dielectric_is_lit_like_lambert() {
+        let w = World::new();
+        let shape = &w.objects()[0];
+        let light = PointLight::new(Point::new(0.0, 0.0, -10.0), Tuple::new(1.0, 1.0, 1.0, 0.0));
+        // an index of 1 reflects nothing head on, so all the light is diffuse
+        let m = pbr(Tuple::new(1.0, 1.0, 1.0, 0.0), 0.0, 1.0, 1.0);
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+        let c = lighting(&m, &light, Point::new(0.0, 0.0, 0.0), eyev, normalv, &light.samples(Point::new(0.0, 0.0, 0.0)), shape.as_trait());
+        assert!(float_eq(c.x, 1.1) && float_eq(c.y, 1.1) && float_eq(c.z, 1.1));
+        assert!(c == pbr_lighting(&m, &light, Point::new(0.0, 0.0, 0.0), eyev, normalv, &light.samples(Point::new(0.0, 0.0, 0.0)), shape.as_trait()));
+
+        let mut hidden = light.samples(Point::new(0.0, 0.0, 0.0));
+        hidden[0].visible = false;
+        let in_shadow = lighting(&m, &light, Point::new(0.0, 0.0, 0.0), eyev, normalv, &hidden, shape.as_trait());
+        assert!(float_eq(in_shadow.x, 0.1));
+    }
+
+    #[test]
+    fn metals_tint_their_highlight_and_roughness_spreads_it() {
+        let w = World::new();
+        let shape = &w.objects()[0];
+        let gold = Tuple::new(1.0, 0.5, 0.0, 0.0);
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+        let p = Point::new(0.0, 0.0, 0.0);
+        let facing = PointLight::new(Point::new(0.0, 0.0, -10.0), Tuple::new(1.0, 1.0, 1.0, 0.0));
+        let aside = PointLight::new(Point::new(0.0, 10.0, -10.0), Tuple::new(1.0, 1.0, 1.0, 0.0));
+
+        let mut smooth = pbr(gold, 1.0, 0.2, 1.5);
+        smooth.ambient = 0.0;
+        let mut rough = smooth.clone();
+        rough.roughness = 0.7;
+
+        // no diffuse, the head on reflection has the colour of the metal
+        let peak = lighting(&smooth, &facing, p, eyev, normalv, &facing.samples(p), shape.as_trait());
+        assert!(float_eq(peak.y, 0.5 * peak.x) && float_eq(peak.z, 0.0));
+        assert!(peak.x > lighting(&rough, &facing, p, eyev, normalv, &facing.samples(p), shape.as_trait()).x);
+
+        // away from the mirror direction the rough metal still shines, the smooth one barely
+        let off_smooth = lighting(&smooth, &aside, p, eyev, normalv, &aside.samples(p), shape.as_trait());
+        let off_rough = lighting(&rough, &aside, p, eyev, normalv, &aside.samples(p), shape.as_trait());
+        assert!(off_rough.x > 10.0 * off_smooth.x);
+
+        // phong materials are untouched
+        let m = Material::default();
+        let c = lighting(&m, &facing, p, eyev, normalv, &facing.samples(p), shape.as_trait());
+        assert!(c == phong_lighting(&m, &facing, p, eyev, normalv, &facing.samples(p), shape.as_trait()));
+    }
+
+    #[test]
+    fn light_through_coloured_glass_is_absorbed_along_the_way() {
+        let mut w = World::new();
+        w.objects_mut().clear();
+        let mut glass = Sphere::default();
+        let mut m = Material::new(Tuple::new(1.0, 1.0, 1.0, 0.0), 0.0, 0.0, 0.0, 200.0);
+        m.transparency = 1.0;
+        m.absorption_color = Tuple::new(1.0, 0.5, 0.5, 0.0);
+        m.absorption_density = 1.0;
+        glass.set_material(m);
+        w.add_object(Box::new(glass));
+        // a wall behind the glass lit only by its ambient term
+        let mut wall = plane();
+        wall.material = Material::new(Tuple::new(1.0, 1.0, 1.0, 0.0), 1.0, 0.0, 0.0, 200.0);
+        wall.set_transform(Transform::new().rotate_x(std::f64::consts::PI / 2.0).translate(0.0, 0.0, 5.0));
+        w.add_object(Box::new(wall));
+
+        // two units of glass through the middle, 1.6 a bit off center
+        let through_center = w.color_at_ray(&Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)), 3);
+        assert!(f64::abs(through_center.x - 1.0) < 0.001);
+        assert!(f64::abs(through_center.y - 0.25) < 0.001 && f64::abs(through_center.z - 0.25) < 0.001);
+        let off_center = w.color_at_ray(&Ray::new(Point::new(0.6, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)), 3);
+        assert!(f64::abs(off_center.y - f64::powf(0.5, 1.6)) < 0.001);
+    }
+
+    #[test]
+    fn glossy_directions_stay_in_a_lobe_in_front_of_the_surface() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let center = Vector::new(1.0, 1.0, 0.0).normalize();
+        assert!(glossy_direction(center, normal, 0.0, 0.3, 0.7) == center);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let d = glossy_direction(center, normal, 0.3, rng.gen(), rng.gen());
+            assert!(float_eq(d.mag(), 1.0));
+            // at most asin(0.3) away from the mirror direction
+            assert!(d.dot(center) > 0.95);
+            // a wide lobe around a grazing direction is folded back above the surface
+            let grazing = glossy_direction(Vector::new(1.0, 0.01, 0.0), normal, 1.0, rng.gen(), rng.gen());
+            assert!(grazing.dot(normal) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn a_rough_mirror_blurs_what_it_reflects() {
+        let shade = |roughness: f64| {
+            let mut w = World::new();
+            let mut floor = plane();
+            floor.material.reflective = 0.5;
+            floor.material.roughness = roughness;
+            floor.material.gloss_samples = 64;
+            floor.set_transform(Transform::new().translate(0.0, -1.0, 0.0));
+            w.add_object(Box::new(floor));
+            let r = Ray::new(Point::new(0.0, 0.0, -3.0), Vector::new(0.0, -f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0));
+            w.color_at_ray(&r, 2)
+        };
+        let sharp = shade(0.0);
+        // part of the blurred reflection misses the sphere and sees the black background
+        assert!(shade(1.0).x < sharp.x - 0.02);
+    }
+
+    #[test]
+    fn a_rough_surface_traces_exactly_its_gloss_samples() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let center = Vector::new(1.0, 1.0, 0.0).normalize();
+        let mut m = Material::default();
+        assert!(lobe_directions(center, normal, &m, false) == vec![center]);
+
+        m.roughness = 0.3;
+        for samples in [1, 5, 8, 9, 16].iter() {
+            m.gloss_samples = *samples;
+            assert_eq!(lobe_directions(center, normal, &m, false).len(), *samples);
+            // rays that already came off a rough surface do not branch again
+            assert_eq!(lobe_directions(center, normal, &m, true).len(), 1);
+        }
+    }
+
+    #[test]
+    fn strata_cover_the_unit_square_once() {
+        for count in 1..20 {
+            for j in 0..40 {
+                for i in 0..40 {
+                    let (x, y) = ((i as f64 + 0.5) / 40.0, (j as f64 + 0.5) / 40.0);
+                    let cells = (0..count).filter(|&k| {
+                        let (x0, y0) = stratum(k, count, 0.0, 0.0);
+                        let (x1, y1) = stratum(k, count, 1.0, 1.0);
+                        x >= x0 && x < x1 && y >= y0 && y < y1
+                    }).count();
+                    assert_eq!(cells, 1);
+                }
+            }
+            // equal areas
+            let (x0, y0) = stratum(count - 1, count, 0.0, 0.0);
+            let (x1, y1) = stratum(count - 1, count, 1.0, 1.0);
+            assert!(float_eq((x1 - x0) * (y1 - y0), 1.0 / count as f64));
+        }
+    }
+
+    #[test]
+    fn facing_rough_mirrors_only_branch_on_the_first_bounce() {
+        let mut w = World::new();
+        w.objects_mut().clear();
+        for y in [-1.0, 1.0].iter() {
+            let mut mirror = plane();
+            mirror.material.reflective = 0.9;
+            mirror.material.roughness = 0.2;
+            mirror.material.gloss_samples = 64;
+            mirror.set_transform(Transform::new().translate(0.0, *y, 0.0));
+            w.add_object(Box::new(mirror));
+        }
+        // 64 rays bouncing five times, 64^5 if every bounce branched
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, -1.0, 0.0).normalize());
+        let c = w.color_at_ray(&r, 5);
+        assert!(c.x > 0.0);
+    }
+
+    #[test]
+    fn pbr_metals_reflect_their_surroundings() {
+        let shade = |spheres: bool| {
+            let mut w = World::new();
+            if !spheres {
+                w.objects_mut().clear();
+            }
+            let mut floor = plane();
+            floor.material = pbr(Tuple::new(1.0, 0.5, 0.0, 0.0), 1.0, 0.0, 1.5);
+            floor.set_transform(Transform::new().translate(0.0, -1.0, 0.0));
+            w.add_object(Box::new(floor));
+            let r = Ray::new(Point::new(0.0, 0.0, -3.0), Vector::new(0.0, -f64::sqrt(2.0) / 2.0, f64::sqrt(2.0) / 2.0));
+            w.color_at_ray(&r, 2)
+        };
+        let reflection = shade(true) - shade(false);
+        // the sphere shows up in the polished floor without any `reflective`, tinted gold
+        assert!(reflection.x > 0.1);
+        assert!(reflection.x > reflection.y && reflection.y > reflection.z);
+    }
     // use crate::intersection::prepare_computations;
     // use crate::tuple::{Point, PointProperties, Vector, VectorProperties};
     // use crate::material::float_eq;